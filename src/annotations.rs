@@ -0,0 +1,207 @@
+//! Annotations for uncovered code, in formats that CI systems understand. This makes uncovered
+//! lines show up inline in the diff view of pull requests.
+
+use std::{
+    io::{self, Write},
+    ops::RangeInclusive,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize;
+
+use crate::{cli::ChangeScope, git::Changes, templates::FileInfo};
+
+/// A range of consecutive uncovered lines in a single file.
+pub struct Annotation<'a> {
+    /// File path relative to the project root.
+    pub path: &'a Utf8Path,
+    /// Uncovered lines, starting at 1.
+    pub lines: RangeInclusive<usize>,
+}
+
+/// Collect the ranges of uncovered lines of all files.
+///
+/// If changes are given, the annotations are limited to the changed lines or files, depending on
+/// the scope.
+pub fn collect<'a>(
+    files: &'a [FileInfo],
+    changes: Option<(&Changes, ChangeScope)>,
+) -> Vec<Annotation<'a>> {
+    files
+        .iter()
+        .flat_map(|file| {
+            let mut lines = file.uncovered_lines();
+
+            if let Some((changes, scope)) = changes {
+                match (scope, changes.get(&file.relative_path)) {
+                    (_, None) => lines.clear(),
+                    (ChangeScope::Files, Some(_)) => {}
+                    (ChangeScope::Lines, Some(file_changes)) => {
+                        lines.retain(|line| file_changes.contains(line));
+                    }
+                }
+            }
+
+            line_ranges(lines).into_iter().map(|lines| Annotation {
+                path: &file.relative_path,
+                lines,
+            })
+        })
+        .collect()
+}
+
+/// Group the given lines into ranges of consecutive lines.
+pub fn line_ranges(lines: impl IntoIterator<Item = usize>) -> Vec<RangeInclusive<usize>> {
+    lines.into_iter().fold(Vec::new(), |mut ranges, line| {
+        match ranges.last_mut() {
+            Some(range) if *range.end() + 1 == line => *range = *range.start()..=line,
+            _ => ranges.push(line..=line),
+        }
+        ranges
+    })
+}
+
+/// Print the annotations as [workflow commands] for GitHub Actions.
+///
+/// GitHub expects paths relative to the repository root, so they're prefixed with the location of
+/// the project inside the repository.
+///
+/// [workflow commands]: https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#setting-a-warning-message
+pub fn print_github(
+    out: &mut impl Write,
+    repo_prefix: &Utf8Path,
+    annotations: &[Annotation<'_>],
+) -> io::Result<()> {
+    for annotation in annotations {
+        writeln!(
+            out,
+            "::warning file={},line={},endLine={}::Uncovered lines",
+            escape_property(repo_prefix.join(annotation.path).as_str()),
+            annotation.lines.start(),
+            annotation.lines.end(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Escape a property value of a GitHub workflow command.
+fn escape_property(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+/// Create a [Code Quality report] for GitLab from the annotations. Like for GitHub, the paths are
+/// relative to the repository root.
+///
+/// [Code Quality report]: https://docs.gitlab.com/ee/ci/testing/code_quality.html#implement-a-custom-tool
+pub fn gitlab_report(
+    repo_prefix: &Utf8Path,
+    annotations: &[Annotation<'_>],
+) -> serde_json::Result<String> {
+    #[derive(Serialize)]
+    struct Issue {
+        description: &'static str,
+        check_name: &'static str,
+        fingerprint: String,
+        severity: &'static str,
+        location: Location,
+    }
+
+    #[derive(Serialize)]
+    struct Location {
+        path: Utf8PathBuf,
+        lines: Lines,
+    }
+
+    #[derive(Serialize)]
+    struct Lines {
+        begin: usize,
+        end: usize,
+    }
+
+    let issues = annotations
+        .iter()
+        .map(|annotation| {
+            let path = repo_prefix.join(annotation.path);
+            Issue {
+                description: "Uncovered lines",
+                check_name: "uncovered-lines",
+                fingerprint: format!(
+                    "{}:{}-{}",
+                    path,
+                    annotation.lines.start(),
+                    annotation.lines.end()
+                ),
+                severity: "minor",
+                location: Location {
+                    path,
+                    lines: Lines {
+                        begin: *annotation.lines.start(),
+                        end: *annotation.lines.end(),
+                    },
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_string_pretty(&issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+
+    use camino::{Utf8Path, Utf8PathBuf};
+    use indoc::indoc;
+
+    use crate::{cli::ChangeScope, git::Changes, templates::FileInfo};
+
+    fn files() -> [FileInfo; 1] {
+        [FileInfo {
+            covered: HashMap::from([(1, 1), (4, 1)]),
+            uncovered: HashSet::from([2, 3, 4, 7, 8]),
+            ..FileInfo::fixture("src/file.rs")
+        }]
+    }
+
+    #[test]
+    fn print_github() {
+        let files = files();
+        let mut output = Vec::new();
+        super::print_github(
+            &mut output,
+            Utf8Path::new("crates/app"),
+            &super::collect(&files, None),
+        )
+        .unwrap();
+
+        let expect = indoc! {"
+            ::warning file=crates/app/src/file.rs,line=2,endLine=3::Uncovered lines
+            ::warning file=crates/app/src/file.rs,line=7,endLine=8::Uncovered lines
+        "};
+
+        assert_eq!(expect, String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn restrict_to_changes() {
+        let files = files();
+        let changes = Changes::from([(Utf8PathBuf::from("src/file.rs"), BTreeSet::from([3, 8]))]);
+
+        let lines = |scope| {
+            super::collect(&files, Some((&changes, scope)))
+                .into_iter()
+                .map(|annotation| annotation.lines)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![3..=3, 8..=8], lines(ChangeScope::Lines));
+        assert_eq!(vec![2..=3, 7..=8], lines(ChangeScope::Files));
+        assert!(super::collect(&files, Some((&Changes::new(), ChangeScope::Files))).is_empty());
+    }
+}
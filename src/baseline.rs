@@ -0,0 +1,154 @@
+//! Comparison of the coverage against a previous report, the baseline, to find regressions.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use camino::Utf8PathBuf;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{cli::Metric, schema::Summary, templates::FileInfo};
+
+/// Condensed coverage information of a single run, that is saved as summary export and can be
+/// loaded again as baseline for later runs.
+#[derive(Deserialize, Serialize)]
+pub struct Baseline {
+    /// Coverage of the whole project.
+    pub totals: Summary,
+    /// Coverage per file, with paths relative to the project root.
+    pub files: BTreeMap<Utf8PathBuf, FileBaseline>,
+}
+
+/// Coverage information of a single file, as part of the [`Baseline`].
+#[derive(Deserialize, Serialize)]
+pub struct FileBaseline {
+    pub summary: Summary,
+    /// Set of lines that were not executed.
+    pub uncovered: BTreeSet<usize>,
+}
+
+/// Difference in coverage percentage between the current run and the baseline.
+#[derive(Clone, Copy)]
+pub struct Delta {
+    pub lines: f64,
+    pub functions: f64,
+    pub regions: f64,
+}
+
+impl Delta {
+    fn new(current: &Summary, baseline: &Summary) -> Self {
+        Self {
+            lines: current.lines.percent - baseline.lines.percent,
+            functions: current.functions.percent - baseline.functions.percent,
+            regions: current.regions.percent - baseline.regions.percent,
+        }
+    }
+}
+
+impl Baseline {
+    /// Create a baseline from the coverage of the current run.
+    pub fn new(files: &[FileInfo], totals: &Summary) -> Self {
+        Self {
+            totals: totals.clone(),
+            files: files
+                .iter()
+                .map(|file| {
+                    (
+                        file.relative_path.clone(),
+                        FileBaseline {
+                            summary: file.summary.clone(),
+                            uncovered: file.uncovered_lines(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Difference of the total coverage compared to the baseline.
+    pub fn total_delta(&self, totals: &Summary) -> Delta {
+        Delta::new(totals, &self.totals)
+    }
+
+    /// Difference of a single file's coverage compared to the baseline, or nothing if the file
+    /// didn't exist in the baseline.
+    pub fn file_delta(&self, file: &FileInfo) -> Option<Delta> {
+        self.files
+            .get(&file.relative_path)
+            .map(|baseline| Delta::new(&file.summary, &baseline.summary))
+    }
+
+    /// Lines of a file that are uncovered now, but weren't in the baseline. For new files, all
+    /// uncovered lines are considered new.
+    pub fn newly_uncovered(&self, file: &FileInfo) -> BTreeSet<usize> {
+        let mut lines = file.uncovered_lines();
+
+        if let Some(baseline) = self.files.get(&file.relative_path) {
+            lines.retain(|line| !baseline.uncovered.contains(line));
+        }
+
+        lines
+    }
+
+    /// Find all metrics of the total coverage, that dropped by more than the given tolerance (in
+    /// percentage points), together with the amount of the drop.
+    pub fn regressions(&self, totals: &Summary, tolerance: f64) -> Vec<(Metric, f64)> {
+        Metric::value_variants()
+            .iter()
+            .filter_map(|&metric| {
+                let drop = metric.percent(&self.totals) - metric.percent(totals);
+                (drop > tolerance).then_some((metric, drop))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::Baseline;
+    use crate::{
+        cli::Metric,
+        schema::{CoverageCounts, Summary},
+        templates::FileInfo,
+    };
+
+    fn file(name: &str, uncovered: &[usize]) -> FileInfo {
+        let count = 5;
+        let covered = count - uncovered.len() as u64;
+
+        FileInfo {
+            summary: Summary {
+                lines: CoverageCounts {
+                    count,
+                    covered,
+                    percent: crate::schema::percent(covered, count),
+                },
+                ..Summary::default()
+            },
+            uncovered: uncovered.iter().copied().collect(),
+            ..FileInfo::fixture(name)
+        }
+    }
+
+    #[test]
+    fn compare() {
+        let old = [file("src/a.rs", &[1])];
+        let old = Baseline::new(&old, &old.iter().map(|f| &f.summary).sum());
+
+        let new = [file("src/a.rs", &[1, 2]), file("src/b.rs", &[4])];
+        let totals = new.iter().map(|f| &f.summary).sum();
+
+        let delta = old.file_delta(&new[0]).unwrap();
+        assert!((delta.lines + 20.0).abs() < 1e-9);
+        assert!(old.file_delta(&new[1]).is_none());
+
+        assert_eq!(BTreeSet::from([2]), old.newly_uncovered(&new[0]));
+        assert_eq!(BTreeSet::from([4]), old.newly_uncovered(&new[1]));
+
+        let regressions = old.regressions(&totals, 1.0);
+        assert_eq!(1, regressions.len());
+        assert!(matches!(regressions[0].0, Metric::Lines));
+        assert!(old.regressions(&totals, 20.0).is_empty());
+    }
+}
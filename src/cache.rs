@@ -0,0 +1,225 @@
+//! Cache for rendered source pages, to speed up repeated report generation.
+//!
+//! Highlighting and minifying the source views is the most expensive part of generating a report.
+//! Therefore, each page is stored together with a key, that is derived from all the inputs that
+//! influence the page. Pages with a matching key can be reused as-is in the next run.
+
+use std::{
+    collections::HashMap,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    schema::{Function, Summary},
+    templates::FileInfo,
+};
+
+/// Name of the cache file inside the output directory.
+const FILE_NAME: &str = ".cache.json";
+
+/// Previously rendered source pages, keyed by their path relative to the project root.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Cache {
+    /// Version of the program that created the cache. Caches of other versions are discarded, as
+    /// the templates might have changed in the meantime.
+    version: String,
+    pages: HashMap<Utf8PathBuf, Page>,
+}
+
+/// A single rendered source page.
+#[derive(Deserialize, Serialize)]
+pub struct Page {
+    /// Hash over all inputs that were used to render the page.
+    pub key: u64,
+    pub content: String,
+}
+
+impl Cache {
+    /// Load the cache from the output directory.
+    ///
+    /// As the cache is only an optimization, any failure to load it (including a missing file)
+    /// results in an empty cache.
+    pub fn load(output_dir: &Utf8Path) -> Self {
+        fs::read(output_dir.join(FILE_NAME))
+            .ok()
+            .and_then(|content| serde_json::from_slice::<Self>(&content).ok())
+            .filter(|cache| cache.version == env!("CARGO_PKG_VERSION"))
+            .unwrap_or_default()
+    }
+
+    /// Create a new cache from the given pages.
+    pub fn new(pages: impl IntoIterator<Item = (Utf8PathBuf, Page)>) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            pages: pages.into_iter().collect(),
+        }
+    }
+
+    /// Save the cache to the output directory.
+    pub fn save(&self, output_dir: &Utf8Path) -> Result<()> {
+        fs::write(output_dir.join(FILE_NAME), serde_json::to_vec(self)?)
+            .wrap_err("failed writing page cache")
+    }
+
+    /// Get the content of a page, if it exists and was rendered from the same inputs.
+    pub fn get(&self, path: &Utf8Path, key: u64) -> Option<&str> {
+        self.pages
+            .get(path)
+            .filter(|page| page.key == key)
+            .map(|page| page.content.as_str())
+    }
+}
+
+/// Calculate the cache key for a source page, from the file content, its coverage information and
+/// any further options that influence the rendering.
+///
+/// The functions of the report are included as well, as their locations and names make up the
+/// outline of the page.
+pub fn page_key(info: &FileInfo, source: &str, functions: &[Function], options: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    source.hash(&mut hasher);
+    info.relative_path.hash(&mut hasher);
+    hash_summary(&info.summary, &mut hasher);
+    sorted(&info.covered).hash(&mut hasher);
+    sorted(&info.uncovered).hash(&mut hasher);
+    sorted(&info.called).hash(&mut hasher);
+    sorted(&info.uncalled).hash(&mut hasher);
+    sorted(&info.branches).hash(&mut hasher);
+    info.changed.hash(&mut hasher);
+    sorted(
+        functions
+            .iter()
+            .filter(|function| function.filenames[0] == info.path)
+            .map(|function| {
+                let regions = function
+                    .regions
+                    .iter()
+                    .map(|region| (region.start, region.end, region.file_id))
+                    .collect::<Vec<_>>();
+                (function.name.as_str(), regions)
+            }),
+    )
+    .hash(&mut hasher);
+    options.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/// Sort the items of an unordered collection, so they can be hashed deterministically.
+fn sorted<T: Ord>(items: impl IntoIterator<Item = T>) -> Vec<T> {
+    let mut items = items.into_iter().collect::<Vec<_>>();
+    items.sort_unstable();
+    items
+}
+
+fn hash_summary(summary: &Summary, hasher: &mut impl Hasher) {
+    for (count, covered, percent) in [
+        (
+            summary.lines.count,
+            summary.lines.covered,
+            summary.lines.percent,
+        ),
+        (
+            summary.functions.count,
+            summary.functions.covered,
+            summary.functions.percent,
+        ),
+        (
+            summary.regions.count,
+            summary.regions.covered,
+            summary.regions.percent,
+        ),
+        (
+            summary.branches.count,
+            summary.branches.covered,
+            summary.branches.percent,
+        ),
+    ] {
+        count.hash(hasher);
+        covered.hash(hasher);
+        percent.to_bits().hash(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use camino::Utf8Path;
+
+    use super::{Cache, Page};
+    use crate::{
+        schema::{Function, Region, RegionKind},
+        templates::FileInfo,
+    };
+
+    #[test]
+    fn page_key() {
+        let mut info = FileInfo {
+            covered: HashMap::from([(1, 1), (2, 1), (3, 5)]),
+            uncovered: HashSet::from([4, 5, 6]),
+            ..FileInfo::fixture("src/file.rs")
+        };
+
+        let mut functions = [Function {
+            name: "file::main".to_owned(),
+            count: 1,
+            regions: vec![Region {
+                start: (1, 1),
+                end: (1, 13),
+                execution_count: 1,
+                file_id: 0,
+                expanded_file_id: 0,
+                kind: RegionKind::Code,
+            }],
+            branches: Vec::new(),
+            filenames: ["/home/user/project/src/file.rs".into()],
+        }];
+
+        let key = super::page_key(&info, "fn main() {}", &functions, true);
+
+        assert_eq!(
+            key,
+            super::page_key(&info, "fn main() {}", &functions, true)
+        );
+        assert_ne!(
+            key,
+            super::page_key(&info, "fn main() {}", &functions, false)
+        );
+        assert_ne!(
+            key,
+            super::page_key(&info, "fn main() { }", &functions, true)
+        );
+        assert_ne!(key, super::page_key(&info, "fn main() {}", &[], true));
+
+        functions[0].regions[0].end = (3, 2);
+        assert_ne!(
+            key,
+            super::page_key(&info, "fn main() {}", &functions, true)
+        );
+        functions[0].regions[0].end = (1, 13);
+
+        info.uncovered.remove(&6);
+        assert_ne!(
+            key,
+            super::page_key(&info, "fn main() {}", &functions, true)
+        );
+
+        let cache = Cache::new([(
+            info.relative_path.clone(),
+            Page {
+                key,
+                content: "page".to_owned(),
+            },
+        )]);
+
+        assert_eq!(Some("page"), cache.get(Utf8Path::new("src/file.rs"), key));
+        assert_eq!(None, cache.get(Utf8Path::new("src/file.rs"), key + 1));
+    }
+}
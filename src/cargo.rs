@@ -1,4 +1,8 @@
-use std::process::Command;
+use std::{
+    ffi::OsString,
+    io::BufReader,
+    process::{Command, ExitStatus, Stdio},
+};
 
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::{
@@ -8,6 +12,8 @@ use color_eyre::{
 use semver::Version;
 use serde::Deserialize;
 
+use crate::schema::JsonExport;
+
 /// Locate the root directory of the project under the current working directory.
 pub fn project_dir(manifest_path: Option<&Utf8Path>) -> Result<Utf8PathBuf> {
     let manifest_path = match manifest_path {
@@ -37,6 +43,111 @@ pub fn output_dir(manifest_path: Option<&Utf8Path>) -> Result<Utf8PathBuf> {
         .wrap_err("failed to load project metadata")
 }
 
+/// Guess the root directory of the project from the files in the coverage data, without the help
+/// of `cargo`.
+///
+/// This is the closest directory with a `Cargo.toml` file, that contains all the files.
+pub fn guess_project_dir<'a>(files: impl IntoIterator<Item = &'a Utf8Path>) -> Result<Utf8PathBuf> {
+    let common = common_dir(files)
+        .ok_or_else(|| eyre!("the coverage data doesn't contain any absolute file paths"))
+        .suggestion("pass the project directory with `--project-dir`")?;
+
+    common
+        .ancestors()
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .map(Utf8Path::to_owned)
+        .ok_or_else(|| eyre!("no directory above {common:?} contains a `Cargo.toml` file"))
+        .suggestion("pass the project directory with `--project-dir`")
+}
+
+/// Find the deepest directory that contains all the given files.
+///
+/// Relative paths are ignored, as they're already relative to the (unknown) project directory.
+/// Returns `None` if there are no absolute paths at all.
+fn common_dir<'a>(files: impl IntoIterator<Item = &'a Utf8Path>) -> Option<Utf8PathBuf> {
+    files
+        .into_iter()
+        .filter(|file| file.is_absolute())
+        .filter_map(Utf8Path::parent)
+        .fold(None, |common: Option<&Utf8Path>, dir| {
+            Some(match common {
+                Some(common) => common
+                    .ancestors()
+                    .find(|ancestor| dir.starts_with(ancestor))
+                    .unwrap_or(common),
+                None => dir,
+            })
+        })
+        .map(Utf8Path::to_owned)
+}
+
+/// A single crate, that is a member of the project's workspace.
+pub struct Package {
+    /// Name of the crate.
+    pub name: String,
+    /// Root directory of the crate, which is where its `Cargo.toml` is located.
+    pub root: Utf8PathBuf,
+}
+
+/// Locate all crates that are part of the project's workspace.
+pub fn packages(manifest_path: Option<&Utf8Path>) -> Result<Vec<Package>> {
+    let manifest_path = match manifest_path {
+        Some(path) => path.to_owned(),
+        None => cargo_locate_project().wrap_err("failed to locate project")?,
+    };
+
+    cargo_metadata(&manifest_path)
+        .map(|meta| {
+            meta.packages
+                .into_iter()
+                .filter_map(|package| {
+                    Some(Package {
+                        name: package.name,
+                        root: package.manifest_path.parent()?.to_owned(),
+                    })
+                })
+                .collect()
+        })
+        .wrap_err("failed to load project metadata")
+}
+
+/// Run `cargo llvm-cov` with the given extra arguments, and parse the JSON export from its output
+/// while it's running.
+///
+/// Any output of the build and tests is forwarded to the terminal as usual. If `cargo llvm-cov`
+/// fails (for example due to failing tests), its exit status is returned instead of the export.
+pub fn llvm_cov(
+    manifest_path: Option<&Utf8Path>,
+    args: &[OsString],
+) -> Result<Result<JsonExport, ExitStatus>> {
+    let mut child = Command::new("cargo")
+        .args(["llvm-cov", "--json"])
+        .args(
+            manifest_path
+                .into_iter()
+                .flat_map(|path| ["--manifest-path", path.as_str()]),
+        )
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .wrap_err("failed running cargo (llvm-cov)")?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| eyre!("failed capturing the output of cargo (llvm-cov)"))?;
+    let export = JsonExport::from_reader(BufReader::new(stdout));
+    let status = child.wait().wrap_err("failed running cargo (llvm-cov)")?;
+
+    if !status.success() {
+        return Ok(Err(status));
+    }
+
+    export
+        .map(Ok)
+        .wrap_err("failed parsing report data from cargo (llvm-cov)")
+}
+
 /// Use `cargo` to find the root `Cargo.toml` file of the project under the current working
 /// directory.
 fn cargo_locate_project() -> Result<Utf8PathBuf> {
@@ -69,13 +180,24 @@ struct Metadata {
     target_directory: Utf8PathBuf,
     /// The root of the workspace (even if the project isn't a workspace).
     workspace_root: Utf8PathBuf,
+    /// All crates of the workspace. Dependencies are excluded, as they're not needed.
+    packages: Vec<MetadataPackage>,
+}
+
+/// Partial structure of a single package in the `cargo metadata` JSON output.
+#[derive(Deserialize)]
+struct MetadataPackage {
+    /// Name of the crate.
+    name: String,
+    /// Location of the crate's `Cargo.toml` file.
+    manifest_path: Utf8PathBuf,
 }
 
 /// Use `cargo` to get the metadata information of the given project.
 fn cargo_metadata(manifest_path: &Utf8Path) -> Result<Metadata> {
     let output = Command::new("cargo")
         .arg("metadata")
-        .args(["--format-version", "1"])
+        .args(["--format-version", "1", "--no-deps"])
         .args(["--manifest-path", manifest_path.as_str()])
         .output()?;
 
@@ -152,6 +274,50 @@ mod tests {
         super::output_dir(None).unwrap();
     }
 
+    #[test]
+    fn packages() {
+        let packages = super::packages(None).unwrap();
+        assert!(packages
+            .iter()
+            .any(|package| package.name == env!("CARGO_PKG_NAME")));
+    }
+
+    #[test]
+    fn common_dir() {
+        use camino::{Utf8Path, Utf8PathBuf};
+
+        let files = [
+            "/home/user/project/src/main.rs",
+            "/home/user/project/src/cli/mod.rs",
+            "/home/user/project/tests/integration.rs",
+            "src/relative.rs",
+        ];
+
+        assert_eq!(
+            Some(Utf8PathBuf::from("/home/user/project")),
+            super::common_dir(files.map(Utf8Path::new))
+        );
+        assert_eq!(
+            Some(Utf8PathBuf::from("/home/user/project/src")),
+            super::common_dir([Utf8Path::new("/home/user/project/src/main.rs")])
+        );
+        assert_eq!(None, super::common_dir([Utf8Path::new("src/main.rs")]));
+    }
+
+    #[test]
+    fn guess_project_dir() {
+        use camino::Utf8Path;
+
+        let root = Utf8Path::new(env!("CARGO_MANIFEST_DIR"));
+
+        assert_eq!(
+            root,
+            super::guess_project_dir([root.join("src/main.rs").as_path()]).unwrap()
+        );
+        assert!(super::guess_project_dir([Utf8Path::new("/src/main.rs")]).is_err());
+        assert!(super::guess_project_dir([Utf8Path::new("src/main.rs")]).is_err());
+    }
+
     #[test]
     fn cargo_locate_project() {
         super::cargo_locate_project().unwrap();
@@ -1,9 +1,12 @@
 //! Handling of command line arguments.
 
 use std::{
+    ffi::OsString,
     fmt::{self, Display},
     fs::OpenOptions,
     io::{self, BufWriter, Write},
+    net::SocketAddr,
+    str::FromStr,
 };
 
 use camino::{Utf8Path, Utf8PathBuf};
@@ -11,7 +14,7 @@ use clap::{CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
 use clap_complete::Shell;
 use color_eyre::eyre::{ensure, Result, WrapErr};
 
-use crate::styles::Theme;
+use crate::{schema::Summary, styles::Theme};
 
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Parser)]
@@ -29,9 +32,34 @@ pub struct Cli {
     /// Where to place the coverage color marker.
     #[arg(long, default_value_t = CoverageStyle::Line, value_name = "STYLE")]
     pub coverage_style: CoverageStyle,
+    /// Amount of lines on the page with the most executed lines, when using the heatmap coverage
+    /// style.
+    #[arg(long, default_value_t = 100, value_name = "COUNT")]
+    pub hot_lines: usize,
     /// Location of the project's Cargo.toml, in case the default detection isn't sufficient.
     #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
     pub manifest_path: Option<Utf8PathBuf>,
+    /// Root directory of the project, overriding the detection through Cargo.
+    ///
+    /// Source files are collected from this directory, and matched against the files in the
+    /// coverage data.
+    #[arg(long, value_hint = ValueHint::DirPath, value_name = "PATH")]
+    pub project_dir: Option<Utf8PathBuf>,
+    /// Replace a prefix of the file paths in the coverage data, in the form `FROM=TO`.
+    ///
+    /// This allows rendering coverage data that was collected on a different machine (like a CI
+    /// container), where the project was located in another directory. Can be passed multiple
+    /// times, and if several prefixes match, the last one takes precedence.
+    #[arg(long, value_name = "FROM=TO")]
+    pub remap_path_prefix: Vec<PathRemap>,
+    /// Don't run `cargo` or `cargo-llvm-cov`, for environments where they aren't installed.
+    ///
+    /// The project directory is taken from `--project-dir`, or otherwise guessed from the files in
+    /// the coverage data (the closest directory with a `Cargo.toml` that contains all of them).
+    /// The `cargo-llvm-cov` version is only checked if the coverage data contains it, and
+    /// per-crate badges are skipped.
+    #[arg(long, requires = "output_dir", conflicts_with = "manifest_path")]
+    pub offline: bool,
     /// Alternative location to save the report files to, overriding the default.
     ///
     /// By default the report is saved to the `<CARGO_TARGET_DIR>/llvm-cov-pretty` directory, where
@@ -41,12 +69,123 @@ pub struct Cli {
     /// <https://doc.rust-lang.org/cargo/reference/config.html#buildtarget-dir>
     #[arg(long, value_hint = ValueHint::DirPath, value_name = "PATH")]
     pub output_dir: Option<Utf8PathBuf>,
+    /// Render all source pages from scratch, instead of reusing unchanged pages from the previous
+    /// run.
+    ///
+    /// Rendered pages are cached in the output directory, keyed by the source file, its coverage
+    /// and the rendering options.
+    #[arg(long)]
+    pub no_cache: bool,
+    /// Delete the output directory before generating the report, even if it wasn't created by a
+    /// previous run.
+    ///
+    /// By default, only directories that are empty or contain a report from an earlier run are
+    /// deleted, to avoid accidental loss of data.
+    #[arg(long)]
+    pub force: bool,
+    /// Generate a self-contained report as a single HTML file, that includes all source files and
+    /// stylesheets.
+    ///
+    /// This is useful for sharing the report as email attachment or CI artifact. The file can get
+    /// rather large for bigger projects, as everything is embedded.
+    #[arg(long)]
+    pub single_file: bool,
     /// Open the report in the default application after it's been generated.
     #[arg(long)]
     pub open: bool,
+    /// Print a coverage summary to the terminal after the report was generated.
+    ///
+    /// The summary contains the total coverage and the given amount of files with the lowest line
+    /// coverage. Colors are disabled if the output isn't a terminal or the `NO_COLOR` environment
+    /// variable is set.
+    #[arg(
+        long,
+        value_name = "COUNT",
+        num_args = 0..=1,
+        default_missing_value = "10"
+    )]
+    pub summary: Option<usize>,
+    /// Fold runs of covered and non-executable lines in the source views into expandable
+    /// placeholders, keeping the given amount of context lines around uncovered lines.
+    #[arg(
+        long,
+        value_name = "LINES",
+        num_args = 0..=1,
+        default_missing_value = "3"
+    )]
+    pub collapse_covered: Option<usize>,
+    /// Print each source file as plain text, annotated with line numbers and hit counts, to STDOUT
+    /// instead of generating the HTML report.
+    #[arg(long)]
+    pub text: bool,
+    /// Only print uncovered lines in the plain text output, surrounded by the given amount of
+    /// context lines.
+    #[arg(long, requires = "text", value_name = "LINES")]
+    pub text_context: Option<usize>,
+    /// Print GitHub Actions workflow commands for uncovered lines to STDOUT, which makes them show
+    /// up as warnings in pull requests.
+    #[arg(long)]
+    pub github_annotations: bool,
+    /// Show the coverage of lines that changed since the given git revision (patch coverage).
+    ///
+    /// Changed lines are highlighted in the source views, and the index lists all changed files.
+    /// Annotations for uncovered lines are restricted to the changed code as well, which affects
+    /// the GitHub Actions annotations and the uncovered lines in the GitLab Code Quality and SARIF
+    /// exports.
+    #[arg(long, value_name = "REV")]
+    pub diff_base: Option<String>,
+    /// Whether to only annotate changed lines, or all lines of changed files.
+    #[arg(long, default_value_t = ChangeScope::Lines, requires = "diff_base", value_name = "SCOPE")]
+    pub annotations_scope: ChangeScope,
+    /// Compare the coverage against a previous report, and show the differences in the index.
+    ///
+    /// The baseline can either be a coverage file from `llvm-cov`, or a summary that was created
+    /// with `--export summary`.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    pub baseline: Option<Utf8PathBuf>,
+    /// Fail if any metric of the total coverage dropped by more than the given amount of
+    /// percentage points, compared to the baseline.
+    #[arg(long, requires = "baseline", value_name = "PERCENT")]
+    pub max_regression: Option<f64>,
+    /// Record the total coverage of each run in a history file inside the output directory, and
+    /// show a chart of the coverage trend in the index.
+    ///
+    /// Each entry contains the time of the run and the current git commit, if available. Runs
+    /// without this flag keep the recorded history, but don't add to it.
+    #[arg(long)]
+    pub history: bool,
+    /// Read all source files from the given git revision, instead of the working tree.
+    ///
+    /// This avoids mismatches between the sources and the coverage data, when the working tree
+    /// changed since the coverage data was collected.
+    #[arg(long, value_name = "REV")]
+    pub source_rev: Option<String>,
+    /// Warn about source files with uncommitted changes, as they might not match the coverage
+    /// data.
+    #[arg(long, conflicts_with = "source_rev")]
+    pub check_git_status: bool,
+    /// Include source files of the project without any coverage data in the report, as completely
+    /// uncovered files.
+    ///
+    /// Such files are usually modules that are never reached by any test. Their line count is
+    /// estimated from the source (counting all lines except empty ones and comments), and added
+    /// to the total line coverage.
+    #[arg(long)]
+    pub include_unreached: bool,
     /// Skip overlaying regular file coverage with function invocation coverage (in source views).
     #[arg(long)]
     pub skip_function_coverage: bool,
+    /// Additional report formats to write into the output directory, next to the HTML report.
+    ///
+    /// Can be passed multiple times to create several different reports at once.
+    #[arg(long, value_name = "FORMAT")]
+    pub export: Vec<ExportFormat>,
+    /// Text on the left side of the coverage badge.
+    #[arg(long, default_value = "coverage", value_name = "LABEL")]
+    pub badge_label: String,
+    /// Coverage metric that is shown on the coverage badge.
+    #[arg(long, default_value_t = Metric::Lines)]
+    pub badge_metric: Metric,
     /// Input coverage file encoded as JSON, or STDIN if omitted.
     #[arg(value_hint = ValueHint::FilePath)]
     pub input: Option<Utf8PathBuf>,
@@ -57,17 +196,28 @@ pub struct Cli {
 impl Cli {
     /// Parse the command line arguments passed to the program.
     pub fn parse() -> Self {
-        <Self as Parser>::parse()
+        let mut args = std::env::args_os().collect::<Vec<_>>();
+
+        // When invoked as `cargo llvm-cov-pretty`, cargo passes the subcommand name as first
+        // argument.
+        if args.get(1).is_some_and(|arg| arg == env!("CARGO_PKG_NAME")) {
+            args.remove(1);
+        }
+
+        <Self as Parser>::parse_from(args)
     }
 }
 
 /// The way in which to mark source code lines as covered or uncovered.
-#[derive(Clone, Copy, Eq, PartialEq, ValueEnum)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq, ValueEnum)]
 pub enum CoverageStyle {
     /// Highlight the whole source line.
     Line,
     /// Only highlight the gutter (count column).
     Gutter,
+    /// Highlight the gutter, and color covered lines by their execution count on a logarithmic
+    /// scale. Additionally, a page with the most executed lines of the project is created.
+    Heatmap,
 }
 
 impl Display for CoverageStyle {
@@ -75,6 +225,127 @@ impl Display for CoverageStyle {
         f.write_str(match self {
             Self::Line => "line",
             Self::Gutter => "gutter",
+            Self::Heatmap => "heatmap",
+        })
+    }
+}
+
+/// Replacement of a path prefix, to map file paths from one machine to another.
+#[derive(Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct PathRemap {
+    pub from: Utf8PathBuf,
+    pub to: Utf8PathBuf,
+}
+
+impl PathRemap {
+    /// Replace the prefix of the path with the last matching remapping, if any.
+    pub fn apply(remaps: &[Self], path: &mut Utf8PathBuf) {
+        let remapped = remaps.iter().rev().find_map(|remap| {
+            path.strip_prefix(&remap.from)
+                .ok()
+                .map(|rest| remap.to.join(rest))
+        });
+
+        if let Some(remapped) = remapped {
+            *path = remapped;
+        }
+    }
+}
+
+impl FromStr for PathRemap {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s.rsplit_once('=').ok_or("expected the form `FROM=TO`")?;
+
+        if from.is_empty() {
+            return Err("the prefix to replace must not be empty");
+        }
+
+        Ok(Self {
+            from: from.into(),
+            to: to.into(),
+        })
+    }
+}
+
+/// Machine-readable report formats for consumption by other tools.
+#[derive(Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    /// `SonarQube`'s generic test coverage format, saved as `sonarqube.xml`.
+    Sonarqube,
+    /// GitLab Code Quality report with uncovered lines, saved as `gl-code-quality-report.json`.
+    Gitlab,
+    /// SARIF log with uncovered functions and lines, saved as `coverage.sarif`.
+    Sarif,
+    /// SVG badge with the total coverage, saved as `badge.svg`. Additionally, a badge for each
+    /// crate of the workspace is saved as `badges/<crate>.svg`.
+    Badge,
+    /// Coverage summary per file, saved as `coverage-summary.json`. It can be used as baseline for
+    /// later runs.
+    Summary,
+}
+
+impl ExportFormat {
+    /// Name of the file that the report is written to, inside the output directory.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Sonarqube => "sonarqube.xml",
+            Self::Gitlab => "gl-code-quality-report.json",
+            Self::Sarif => "coverage.sarif",
+            Self::Badge => "badge.svg",
+            Self::Summary => "coverage-summary.json",
+        }
+    }
+}
+
+/// The amount of changed code that is considered, when restricting output to changes only.
+#[derive(Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub enum ChangeScope {
+    /// Only lines that changed.
+    Lines,
+    /// All lines of files that changed.
+    Files,
+}
+
+impl Display for ChangeScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Lines => "lines",
+            Self::Files => "files",
+        })
+    }
+}
+
+/// One of the different kinds of coverage, that are collected for each file.
+#[derive(Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub enum Metric {
+    /// Percentage of covered lines.
+    Lines,
+    /// Percentage of called functions.
+    Functions,
+    /// Percentage of covered code regions.
+    Regions,
+}
+
+impl Metric {
+    /// Pick the coverage percentage that corresponds to this metric.
+    pub fn percent(self, summary: &Summary) -> f64 {
+        match self {
+            Self::Lines => summary.lines.percent,
+            Self::Functions => summary.functions.percent,
+            Self::Regions => summary.regions.percent,
+        }
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Lines => "lines",
+            Self::Functions => "functions",
+            Self::Regions => "regions",
         })
     }
 }
@@ -93,6 +364,25 @@ pub enum Command {
         #[arg(value_hint = ValueHint::DirPath)]
         dir: Utf8PathBuf,
     },
+    /// Run `cargo llvm-cov` with the given arguments, and generate the report from its output.
+    ///
+    /// The `--json` flag is added automatically, as well as `--manifest-path` if it was given.
+    /// Failing tests are reported through the exit code, without generating a report.
+    Run {
+        /// Arguments passed to `cargo llvm-cov`, usually after a `--` separator.
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+    /// Serve the report on a local HTTP server, and regenerate it whenever the input file or any
+    /// of the source files change.
+    ///
+    /// Open pages in the browser are reloaded automatically after each regeneration. All options
+    /// for the report apply as usual, but an input file is required.
+    Serve {
+        /// Address to listen on for HTTP connections.
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: SocketAddr,
+    },
 }
 
 /// Generate shell completions for the given shell variant and write the to STDOUT.
@@ -147,4 +437,31 @@ mod tests {
         use clap::CommandFactory;
         super::Cli::command().debug_assert();
     }
+
+    #[test]
+    fn path_remap() {
+        use camino::Utf8PathBuf;
+
+        use super::PathRemap;
+
+        let remaps = [
+            "/build=/home/user".parse::<PathRemap>().unwrap(),
+            "/build/src=/home/user/project".parse().unwrap(),
+        ];
+
+        let mut path = Utf8PathBuf::from("/build/src/main.rs");
+        PathRemap::apply(&remaps, &mut path);
+        assert_eq!("/home/user/project/main.rs", path);
+
+        let mut path = Utf8PathBuf::from("/build/tests/integration.rs");
+        PathRemap::apply(&remaps, &mut path);
+        assert_eq!("/home/user/tests/integration.rs", path);
+
+        let mut path = Utf8PathBuf::from("/builder/src/main.rs");
+        PathRemap::apply(&remaps, &mut path);
+        assert_eq!("/builder/src/main.rs", path);
+
+        assert!("/build".parse::<PathRemap>().is_err());
+        assert!("=/build".parse::<PathRemap>().is_err());
+    }
 }
@@ -0,0 +1,368 @@
+//! Helpers to query change information from `git`.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::{
+    eyre::{eyre, Result, WrapErr},
+    Help, SectionExt,
+};
+
+/// Mapping from file paths (relative to the project root) to the set of lines that changed.
+pub type Changes = HashMap<Utf8PathBuf, BTreeSet<usize>>;
+
+/// Use `git` to find all lines that were added or modified since the given revision, including
+/// any uncommitted changes in the working tree.
+pub fn changed_lines(project_dir: &Utf8Path, base: &str) -> Result<Changes> {
+    let output = Command::new("git")
+        .current_dir(project_dir)
+        .args([
+            "diff",
+            "--unified=0",
+            "--no-color",
+            "--no-ext-diff",
+            "--relative",
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+        ])
+        .arg(base)
+        .output()
+        .wrap_err("failed running git")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!("failed running git (diff)")
+            .with_section(move || stderr.to_string().header("Stderr:"))
+            .suggestion("ensure the project is a git repository and the revision exists"));
+    }
+
+    Ok(parse_diff(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Get the hash of the currently checked out commit, or nothing if the project is not a git
+/// repository or `git` is not available.
+pub fn head_commit(project_dir: &Utf8Path) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(project_dir)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Get the root directory of the git repository that contains the project, or nothing if the
+/// project is not a git repository or `git` is not available.
+pub fn toplevel(project_dir: &Utf8Path) -> Option<Utf8PathBuf> {
+    let output = Command::new("git")
+        .current_dir(project_dir)
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| Utf8PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// Use `git` to list all Rust source files (relative to the project root) at the given revision.
+pub fn source_files(project_dir: &Utf8Path, rev: &str) -> Result<Vec<Utf8PathBuf>> {
+    let output = Command::new("git")
+        .current_dir(project_dir)
+        .args(["ls-tree", "-r", "-z", "--name-only"])
+        .arg(rev)
+        .output()
+        .wrap_err("failed running git")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!("failed running git (ls-tree)")
+            .with_section(move || stderr.to_string().header("Stderr:"))
+            .suggestion("ensure the project is a git repository and the revision exists"));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .map(Utf8PathBuf::from)
+        .filter(|path| path.extension() == Some("rs"))
+        .collect())
+}
+
+/// Use `git` to read the content of several files (relative to the project root) at the given
+/// revision, instead of the working tree. All files are read by a single process, and files that
+/// don't exist at the revision are returned as `None`.
+pub fn show_files(
+    project_dir: &Utf8Path,
+    rev: &str,
+    paths: &[&Utf8Path],
+) -> Result<Vec<Option<String>>> {
+    let mut child = Command::new("git")
+        .current_dir(project_dir)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .wrap_err("failed running git")?;
+
+    // The requests are written from a separate thread, as git already answers while reading them,
+    // and would block once the output pipe is full.
+    let requests = paths
+        .iter()
+        .flat_map(|path| [rev, ":./", path.as_str(), "\n"])
+        .collect::<String>();
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| eyre!("failed opening git input"))?;
+    let writer = thread::spawn(move || stdin.write_all(requests.as_bytes()));
+
+    let output = child.wait_with_output().wrap_err("failed running git")?;
+    writer
+        .join()
+        .map_err(|_| eyre!("failed writing git input"))?
+        .wrap_err("failed writing git input")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!("failed running git (cat-file)")
+            .with_section(move || stderr.to_string().header("Stderr:"))
+            .suggestion("ensure the project is a git repository and the revision exists"));
+    }
+
+    parse_batch(&output.stdout, paths.len())
+}
+
+/// Use `git` to find all files (relative to the project root) that have uncommitted changes,
+/// either staged or not.
+pub fn modified_files(project_dir: &Utf8Path) -> Result<HashSet<Utf8PathBuf>> {
+    let output = Command::new("git")
+        .current_dir(project_dir)
+        .args(["diff", "--name-only", "--no-ext-diff", "--relative", "HEAD"])
+        .output()
+        .wrap_err("failed running git")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!("failed running git (diff)")
+            .with_section(move || stderr.to_string().header("Stderr:"))
+            .suggestion("ensure the project is a git repository"));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(Utf8PathBuf::from)
+        .collect())
+}
+
+/// Parse the output of `git cat-file --batch`, which is a header line in the form of
+/// `<oid> <type> <size>` followed by the content for each object, or `<name> missing` if it
+/// doesn't exist.
+fn parse_batch(mut output: &[u8], count: usize) -> Result<Vec<Option<String>>> {
+    let mut contents = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let end = output
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| eyre!("unexpected end of git output"))?;
+        let header = String::from_utf8_lossy(&output[..end]);
+        output = &output[end + 1..];
+
+        if header.ends_with(" missing") {
+            contents.push(None);
+            continue;
+        }
+
+        let size = header
+            .rsplit(' ')
+            .next()
+            .and_then(|size| size.parse::<usize>().ok())
+            .ok_or_else(|| eyre!("invalid git output {header:?}"))?;
+        let content = output
+            .get(..size)
+            .ok_or_else(|| eyre!("unexpected end of git output"))?;
+
+        contents.push(Some(
+            String::from_utf8(content.to_vec()).wrap_err("file content isn't valid UTF-8")?,
+        ));
+        // Each content is followed by an additional newline.
+        output = output.get(size + 1..).unwrap_or_default();
+    }
+
+    Ok(contents)
+}
+
+/// Parse the output of `git diff --unified=0` and collect the changed lines of the new file
+/// versions.
+fn parse_diff(diff: &str) -> Changes {
+    let mut changes = Changes::new();
+    let mut current = None;
+
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            current = diff_path(path);
+        } else if let Some(hunk) = line.strip_prefix("@@ ") {
+            let Some(path) = &current else { continue };
+            let Some((start, count)) = parse_hunk(hunk) else {
+                continue;
+            };
+
+            changes
+                .entry(path.clone())
+                .or_default()
+                .extend(start..start + count);
+        }
+    }
+
+    changes
+}
+
+/// Extract the path from the header line of a changed file in a diff, which is `None` for deleted
+/// files.
+///
+/// Git appends a tab to paths that contain spaces, and quotes paths with special characters,
+/// escaping them like a C string.
+fn diff_path(header: &str) -> Option<Utf8PathBuf> {
+    let path = header.strip_suffix('\t').unwrap_or(header);
+    let path = match path
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+    {
+        Some(quoted) => unquote(quoted),
+        None => path.to_owned(),
+    };
+
+    path.strip_prefix("b/").map(Utf8PathBuf::from)
+}
+
+/// Resolve the escape sequences of a quoted path. Octal escapes are raw bytes of the UTF-8
+/// encoded path.
+fn unquote(quoted: &str) -> String {
+    let mut bytes = Vec::with_capacity(quoted.len());
+    let mut iter = quoted.bytes();
+
+    while let Some(b) = iter.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+
+        bytes.push(match iter.next() {
+            Some(b'a') => 0x07,
+            Some(b'b') => 0x08,
+            Some(b't') => b'\t',
+            Some(b'n') => b'\n',
+            Some(b'v') => 0x0b,
+            Some(b'f') => 0x0c,
+            Some(b'r') => b'\r',
+            Some(first @ b'0'..=b'3') => (&mut iter)
+                .take(2)
+                .fold(first - b'0', |value, digit| value * 8 + (digit - b'0')),
+            Some(other) => other,
+            None => break,
+        });
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Parse a hunk header in the form of `-a,b +c,d @@` and return the start line and line count of
+/// the new file version (`c` and `d`).
+fn parse_hunk(hunk: &str) -> Option<(usize, usize)> {
+    let new = hunk.split(' ').find_map(|part| part.strip_prefix('+'))?;
+
+    Some(match new.split_once(',') {
+        Some((start, count)) => (start.parse().ok()?, count.parse().ok()?),
+        None => (new.parse().ok()?, 1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use camino::Utf8PathBuf;
+    use indoc::indoc;
+
+    #[test]
+    fn parse_batch() {
+        let output = b"ca05282d blob 10\nfn a() {}\n\nHEAD:./src/b.rs missing\nd2c1a0e7 blob 0\n\n";
+
+        assert_eq!(
+            vec![Some("fn a() {}\n".to_owned()), None, Some(String::new())],
+            super::parse_batch(output, 3).unwrap()
+        );
+        assert!(super::parse_batch(b"ca05282d blob 10\nfn a", 1).is_err());
+    }
+
+    #[test]
+    fn parse_diff() {
+        let diff = indoc! {"
+            diff --git a/src/main.rs b/src/main.rs
+            index 90ff371..cd38b7e 100644
+            --- a/src/main.rs
+            +++ b/src/main.rs
+            @@ -16,1 +16,2 @@ use rayon::iter::{IntoParallelIterator};
+            -use a;
+            +use b;
+            +use c;
+            @@ -30,0 +32 @@ fn main() {
+            +    a();
+            @@ -40,2 +42,0 @@ fn main() {
+            -    b();
+            -    c();
+            diff --git a/src/old.rs b/src/old.rs
+            deleted file mode 100644
+            --- a/src/old.rs
+            +++ /dev/null
+            @@ -1,1 +0,0 @@
+            -fn old() {}
+            diff --git a/src/with space.rs b/src/with space.rs
+            --- a/src/with space.rs\t
+            +++ b/src/with space.rs\t
+            @@ -1 +1 @@
+            -a
+            +b
+            diff --git \"a/src/t\\303\\244st.rs\" \"b/src/t\\303\\244st.rs\"
+            --- \"a/src/t\\303\\244st.rs\"
+            +++ \"b/src/t\\303\\244st.rs\"
+            @@ -2,0 +3 @@
+            +b
+            diff --git \"a/src/q\\\"uote.rs\" \"b/src/q\\\"uote.rs\"
+            --- \"a/src/q\\\"uote.rs\"
+            +++ \"b/src/q\\\"uote.rs\"
+            @@ -4,0 +5 @@
+            +c
+        "};
+
+        let changes = super::parse_diff(diff);
+
+        assert_eq!(4, changes.len());
+        assert_eq!(
+            &BTreeSet::from([16, 17, 32]),
+            &changes[&Utf8PathBuf::from("src/main.rs")]
+        );
+        assert_eq!(
+            &BTreeSet::from([1]),
+            &changes[&Utf8PathBuf::from("src/with space.rs")]
+        );
+        assert_eq!(
+            &BTreeSet::from([3]),
+            &changes[&Utf8PathBuf::from("src/täst.rs")]
+        );
+        assert_eq!(
+            &BTreeSet::from([5]),
+            &changes[&Utf8PathBuf::from("src/q\"uote.rs")]
+        );
+    }
+}
@@ -6,8 +6,7 @@
 
 use std::fmt::{Display, Write};
 
-use camino::Utf8Path;
-use color_eyre::eyre::{eyre, Result, WrapErr};
+use color_eyre::eyre::{eyre, Result};
 use syntect::parsing::{ParseState, Scope, ScopeStack, ScopeStackOp, SyntaxSet, SCOPE_REPO};
 
 /// The highlighter is the main component that performs transformation of plain source code into
@@ -28,16 +27,8 @@ impl Highlighter {
         }
     }
 
-    /// Read the file at the given path and turn each line into annotated HTML content.
-    pub fn file_to_spans(&self, file: &Utf8Path, no_highlight: bool) -> Result<Vec<String>> {
-        let content = std::fs::read_to_string(file)
-            .wrap_err_with(|| format!("failed reading file contents from {file:?}"))?;
-
-        self.source_to_spans(&content, no_highlight)
-    }
-
     /// Parse each line of the given content and turn it into annotated HTML content.
-    fn source_to_spans(&self, content: &str, no_highlight: bool) -> Result<Vec<String>> {
+    pub fn source_to_spans(&self, content: &str, no_highlight: bool) -> Result<Vec<String>> {
         let syntax = self
             .ps
             .find_syntax_by_extension("rs")
@@ -0,0 +1,94 @@
+//! Tracking of the total coverage over multiple runs, to show how it evolves over time.
+
+use std::{fs, io::ErrorKind};
+
+use camino::Utf8Path;
+use color_eyre::eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::schema::Summary;
+
+/// Name of the history file inside the output directory.
+pub const FILE_NAME: &str = "history.json";
+
+/// The total coverage of a single run.
+#[derive(Deserialize, Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct Entry {
+    /// Point in time of the run, as Unix timestamp in seconds.
+    pub timestamp: i64,
+    /// Git commit that was checked out during the run, if the project is a git repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    pub totals: Summary,
+}
+
+impl Entry {
+    pub fn new(generated: OffsetDateTime, commit: Option<String>, totals: Summary) -> Self {
+        Self {
+            timestamp: generated.unix_timestamp(),
+            commit,
+            totals,
+        }
+    }
+
+    /// Point in time of the run, in UTC.
+    pub fn datetime(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(self.timestamp).unwrap_or(OffsetDateTime::UNIX_EPOCH)
+    }
+
+    /// Abbreviated form of the commit hash, as shown by git.
+    pub fn short_commit(&self) -> Option<&str> {
+        self.commit
+            .as_deref()
+            .map(|commit| commit.get(..7).unwrap_or(commit))
+    }
+}
+
+/// Load all previous entries from the history file in the output directory. A missing file is
+/// treated as empty history.
+pub fn load(output_dir: &Utf8Path) -> Result<Vec<Entry>> {
+    let path = output_dir.join(FILE_NAME);
+
+    match fs::read(&path) {
+        Ok(content) => serde_json::from_slice(&content)
+            .wrap_err_with(|| format!("failed parsing coverage history from {path:?}")),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).wrap_err_with(|| format!("failed reading coverage history from {path:?}")),
+    }
+}
+
+/// Save the entries to the history file in the output directory.
+pub fn save(output_dir: &Utf8Path, entries: &[Entry]) -> Result<()> {
+    let path = output_dir.join(FILE_NAME);
+
+    fs::write(&path, serde_json::to_string_pretty(entries)?)
+        .wrap_err_with(|| format!("failed writing coverage history to {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::Entry;
+    use crate::schema::Summary;
+
+    #[test]
+    fn roundtrip() {
+        let entry = Entry::new(
+            datetime!(2024-03-01 12:00 UTC),
+            Some("0123456789abcdef".to_owned()),
+            Summary::default(),
+        );
+
+        assert_eq!(datetime!(2024-03-01 12:00 UTC), entry.datetime());
+        assert_eq!(Some("0123456"), entry.short_commit());
+
+        let json = serde_json::to_string(&[&entry]).unwrap();
+        assert_eq!(
+            vec![entry],
+            serde_json::from_str::<Vec<Entry>>(&json).unwrap()
+        );
+    }
+}
@@ -3,7 +3,7 @@
 #![allow(clippy::cast_possible_truncation)]
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, HashSet},
     fs::{self, File},
     io::BufReader,
     ops::RangeInclusive,
@@ -11,20 +11,42 @@ use std::{
 
 use askama::Template;
 use camino::{Utf8Path, Utf8PathBuf};
-use color_eyre::eyre::{Error, Result, WrapErr};
-use rayon::iter::{IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use color_eyre::eyre::{ensure, eyre, Result, WrapErr};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator,
+    IntoParallelRefMutIterator, ParallelIterator,
+};
 use time::{OffsetDateTime, UtcOffset};
 
 use self::{
-    cli::Cli, highlight::Highlighter, minify::Minifier, schema::JsonExport, templates::FileInfo,
+    baseline::Baseline,
+    cache::Cache,
+    cli::{Cli, ExportFormat},
+    highlight::Highlighter,
+    minify::Minifier,
+    schema::JsonExport,
+    search::SearchIndex,
+    templates::{Diagnostics, FileInfo},
 };
 
+mod annotations;
+mod baseline;
+mod cache;
 mod cargo;
 mod cli;
+mod git;
 mod highlight;
+mod history;
 mod minify;
+mod outline;
+mod output;
+mod sarif;
 mod schema;
+mod search;
+mod serve;
+mod summary;
 mod templates;
+mod text;
 
 mod styles {
     #![allow(dead_code)]
@@ -39,119 +61,694 @@ fn main() -> Result<()> {
 
     color_eyre::install()?;
 
-    if let Some(sub) = cli.cmd {
-        match sub {
-            cli::Command::Completions { shell } => cli::completions(shell),
-            cli::Command::Manpages { dir } => cli::manpages(&dir)?,
+    match &cli.cmd {
+        Some(cli::Command::Completions { shell }) => cli::completions(*shell),
+        Some(cli::Command::Manpages { dir }) => cli::manpages(dir)?,
+        Some(cli::Command::Run { args }) => {
+            ensure!(
+                !cli.offline,
+                "running cargo-llvm-cov isn't possible in offline mode"
+            );
+
+            match cargo::llvm_cov(cli.manifest_path.as_deref(), args)? {
+                Ok(export) => run(&cli, offset, export)?,
+                Err(status) => std::process::exit(status.code().unwrap_or(1)),
+            }
         }
+        Some(cli::Command::Serve { address }) => {
+            ensure!(
+                cli.input.is_some(),
+                "serving the report requires an input file, that can be watched for changes"
+            );
+            ensure!(!cli.text, "the plain text output can't be served");
 
-        return Ok(());
+            let report = generate(&cli, offset, load_export(cli.input.as_deref())?, false)?;
+
+            serve::run(
+                *address,
+                &report.output_dir,
+                || {
+                    Ok(cli
+                        .input
+                        .iter()
+                        .cloned()
+                        .chain(
+                            collect_project_files(&report.project_dir)?
+                                .into_iter()
+                                .map(|(absolute, _)| absolute),
+                        )
+                        .collect())
+                },
+                || {
+                    let export = load_export(cli.input.as_deref())?;
+                    generate(&cli, offset, export, true).map(|_| ())
+                },
+            )?;
+        }
+        None => run(&cli, offset, load_export(cli.input.as_deref())?)?,
     }
 
+    Ok(())
+}
+
+/// Generate the report from the coverage data (or print it as plain text), and tell the user where
+/// to find it.
+fn run(cli: &Cli, offset: UtcOffset, export: JsonExport) -> Result<()> {
+    if cli.text {
+        let coverage = load_coverage(cli, export)?;
+        return print_text(cli, &coverage.files, &coverage.sources);
+    }
+
+    let report = generate(cli, offset, export, false)?;
+
+    if cli.open {
+        open::that(&report.index_path)?;
+    } else {
+        println!(
+            "report generated at {:?}",
+            report
+                .index_path
+                .strip_prefix(&report.project_dir)
+                .unwrap_or(&report.index_path)
+        );
+    }
+
+    match cli.max_regression {
+        Some(tolerance) => check_regressions(&report.regressions, tolerance),
+        None => Ok(()),
+    }
+}
+
+/// The coverage data, merged with the information about the project it belongs to.
+struct CoverageData {
+    export: schema::Export,
+    manifest_path: Option<Utf8PathBuf>,
+    project_dir: Utf8PathBuf,
+    output_dir: Utf8PathBuf,
+    files: Vec<FileInfo>,
+    /// Content of each source file, in the same order as the files.
+    sources: Vec<String>,
+    baseline: Option<Baseline>,
+    diagnostics: Diagnostics,
+    /// Changed lines since the diff base, if one was given.
+    changes: Option<git::Changes>,
+}
+
+/// Outcome of generating a report.
+struct Report {
+    project_dir: Utf8PathBuf,
+    output_dir: Utf8PathBuf,
+    index_path: Utf8PathBuf,
+    /// Metrics of the total coverage that dropped compared to the baseline, beyond the tolerance.
+    regressions: Vec<(cli::Metric, f64)>,
+}
+
+/// Load the coverage data and combine it with the project's source files.
+fn load_coverage(cli: &Cli, export: JsonExport) -> Result<CoverageData> {
     let JsonExport {
-        data: [export],
+        data: [mut export],
         cargo_llvm_cov,
         ..
-    } = if let Some(input) = cli.input {
-        let file = BufReader::new(File::open(&input)?);
-        JsonExport::from_reader(file)
-            .wrap_err_with(|| format!("failed parsing report data from {input:?}"))?
-    } else {
-        let stdin = std::io::stdin().lock();
-        JsonExport::from_reader(stdin).wrap_err("failed parsing report data from STDIN")?
-    };
+    } = export;
+
+    remap_paths(&mut export, &cli.remap_path_prefix);
 
-    cargo::check_version(cargo_llvm_cov.as_ref().map(|v| v.version.clone()))
-        .wrap_err("failed checking cargo-llvm-cov version")?;
+    let version = cargo_llvm_cov.as_ref().map(|v| v.version.clone());
+    if !cli.offline || version.is_some() {
+        cargo::check_version(version).wrap_err("failed checking cargo-llvm-cov version")?;
+    }
 
     let manifest_path = cli
         .manifest_path
-        .as_deref()
-        .or(cargo_llvm_cov.as_ref().map(|v| v.manifest_path.as_ref()));
+        .clone()
+        .or(cargo_llvm_cov.map(|v| v.manifest_path));
 
-    let project_dir =
-        cargo::project_dir(manifest_path).wrap_err("failed to locate project directory")?;
-    let output_dir = match cli.output_dir {
-        Some(dir) => dir,
-        None => cargo::output_dir(manifest_path).wrap_err("failed to locate output directory")?,
+    let project_dir = match &cli.project_dir {
+        Some(dir) => dir
+            .canonicalize_utf8()
+            .wrap_err_with(|| format!("failed to resolve project directory {dir:?}"))?,
+        None if cli.offline => {
+            cargo::guess_project_dir(export.files.iter().map(|file| file.filename.as_path()))
+                .wrap_err("failed to derive project directory from the coverage data")?
+        }
+        None => cargo::project_dir(manifest_path.as_deref())
+            .wrap_err("failed to locate project directory")?,
+    };
+    let output_dir = match &cli.output_dir {
+        Some(dir) => dir.clone(),
+        None => cargo::output_dir(manifest_path.as_deref())
+            .wrap_err("failed to locate output directory")?,
+    };
+
+    let project_files = match &cli.source_rev {
+        Some(rev) => collect_revision_files(&project_dir, rev)?,
+        None => collect_project_files(&project_dir)?,
     };
+    let baseline = cli
+        .baseline
+        .as_deref()
+        .map(|path| load_baseline(path, &project_files, &cli.remap_path_prefix))
+        .transpose()?;
+    let mut diagnostics = collect_diagnostics(&project_files, &export.files);
 
-    let files = collect_project_files(&project_dir)?;
-    let mut files = merge_file_info(files, &export.files);
+    let mut files = merge_file_info(project_files, &export.files);
 
     merge_function_info(&mut files, &export.functions);
 
-    files.sort_unstable_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    let unreached = if cli.include_unreached {
+        diagnostics.missing_coverage.as_slice()
+    } else {
+        &[]
+    };
+    let sources = read_sources(
+        cli,
+        &project_dir,
+        files
+            .iter()
+            .map(|file| file.relative_path.as_path())
+            .chain(unreached.iter().map(Utf8PathBuf::as_path)),
+    )?;
 
-    fs::remove_dir_all(&output_dir).ok();
-    fs::create_dir_all(&output_dir)?;
+    if cli.include_unreached {
+        let unreached = unreached
+            .iter()
+            .filter_map(|relative_path| {
+                let source = sources.get(relative_path)?;
+                Some(unreached_file_info(&project_dir, relative_path, source))
+            })
+            .collect::<Vec<_>>();
 
-    fs::write(output_dir.join("style.css"), STYLESHEET.as_bytes())?;
-    fs::write(output_dir.join("syntax.css"), cli.theme.as_bytes())?;
+        export.totals = std::iter::once(&export.totals)
+            .chain(unreached.iter().map(|file| &file.summary))
+            .sum();
+        files.extend(unreached);
+    }
 
-    let minifier = Minifier::new();
-    let highlighter = Highlighter::new();
+    let sources = sort_files(&mut files, sources);
 
-    let index_path = output_dir.join("index.html");
+    diagnostics.stale_sources = find_stale_sources(cli, &project_dir, &files, &sources)?;
+    warn_diagnostics(&project_dir, &diagnostics, cli.include_unreached);
 
-    fs::write(
-        &index_path,
-        minifier.minify(
-            templates::Index {
-                title: "Index",
-                base_dir: "./",
-                generated: OffsetDateTime::now_utc().to_offset(offset),
-                files: &files,
-                totals: &export.totals,
-            }
-            .render()?
-            .as_bytes(),
-        ),
+    let changes = cli
+        .diff_base
+        .as_deref()
+        .map(|base| git::changed_lines(&project_dir, base))
+        .transpose()
+        .wrap_err("failed to collect changed lines")?;
+
+    if let Some(changes) = &changes {
+        for file in &mut files {
+            file.changed = changes
+                .get(&file.relative_path)
+                .cloned()
+                .unwrap_or_default();
+        }
+    }
+
+    Ok(CoverageData {
+        export,
+        manifest_path,
+        project_dir,
+        output_dir,
+        files,
+        sources,
+        baseline,
+        diagnostics,
+        changes,
+    })
+}
+
+/// Generate the HTML report and all additional exports into the output directory.
+///
+/// If `staged` is set, the report is generated next to the output directory first, and only
+/// replaces the previous report once it's complete. That way, the served report stays available
+/// during regenerations.
+fn generate(cli: &Cli, offset: UtcOffset, export: JsonExport, staged: bool) -> Result<Report> {
+    let CoverageData {
+        export,
+        manifest_path,
+        project_dir,
+        output_dir,
+        files,
+        sources,
+        baseline,
+        diagnostics,
+        changes,
+    } = load_coverage(cli, export)?;
+
+    let generated = OffsetDateTime::now_utc().to_offset(offset);
+    let target_dir = if staged {
+        output::staging_dir(&output_dir)
+    } else {
+        output_dir.clone()
+    };
+    let (history, cache) = prepare_output_dir(
+        cli,
+        &project_dir,
+        &output_dir,
+        &target_dir,
+        &export.totals,
+        generated,
     )?;
 
-    files.into_par_iter().try_for_each(|file| {
-        let output = output_dir
-            .join(&file.relative_path)
-            .with_extension("rs.html");
+    write_html(
+        cli,
+        &target_dir,
+        &templates::Index {
+            title: "Index",
+            base_dir: "./",
+            single_file: cli.single_file,
+            generated,
+            files: &files,
+            totals: &export.totals,
+            diff_base: cli.diff_base.as_deref(),
+            baseline: baseline.as_ref(),
+            history: &history,
+            diagnostics: &diagnostics,
+            hot_lines: cli.coverage_style == cli::CoverageStyle::Heatmap,
+        },
+        &sources,
+        &export.functions,
+        &cache,
+    )?;
 
-        if let Some(parent) = output.parent() {
-            fs::create_dir_all(parent)?;
-        }
+    let annotations = annotations::collect(
+        &files,
+        changes
+            .as_ref()
+            .map(|changes| (changes, cli.annotations_scope)),
+    );
+    let repo_prefix = repository_prefix(&project_dir);
+
+    write_exports(
+        cli,
+        manifest_path.as_deref(),
+        &target_dir,
+        &files,
+        &export,
+        &repo_prefix,
+        &annotations,
+    )?;
+
+    if cli.github_annotations {
+        annotations::print_github(&mut std::io::stdout().lock(), &repo_prefix, &annotations)?;
+    }
 
-        let lines = highlighter.file_to_spans(&file.path, cli.no_highlight)?;
+    print_summaries(cli, &files, &export.totals)?;
 
+    if staged {
+        output::swap(&output_dir, &target_dir)?;
+    }
+
+    Ok(Report {
+        regressions: baseline
+            .zip(cli.max_regression)
+            .map(|(baseline, tolerance)| baseline.regressions(&export.totals, tolerance))
+            .unwrap_or_default(),
+        index_path: output_dir.join("index.html"),
+        project_dir,
+        output_dir,
+    })
+}
+
+/// Reset the target directory for a new report, and return the state from previous runs, which is
+/// kept in the output directory. Both are the same, unless the report is staged.
+fn prepare_output_dir(
+    cli: &Cli,
+    project_dir: &Utf8Path,
+    output_dir: &Utf8Path,
+    target_dir: &Utf8Path,
+    totals: &schema::Summary,
+    generated: OffsetDateTime,
+) -> Result<(Vec<history::Entry>, Cache)> {
+    let mut history = history::load(output_dir)?;
+    if cli.history {
+        history.push(history::Entry::new(
+            generated,
+            git::head_commit(project_dir),
+            totals.clone(),
+        ));
+    }
+
+    let cache = if cli.no_cache {
+        Cache::default()
+    } else {
+        Cache::load(output_dir)
+    };
+
+    output::prepare(target_dir, cli.force)?;
+
+    // Keep the history even if it's not recorded in this run, so that it isn't lost by a single run
+    // without `--history`.
+    if !history.is_empty() {
+        history::save(target_dir, &history)?;
+    }
+
+    if !cli.history {
+        history.clear();
+    }
+
+    Ok((history, cache))
+}
+
+/// Location of the project root relative to the root of its git repository, as CI systems expect
+/// paths relative to the repository. Empty if the project isn't part of a git repository.
+fn repository_prefix(project_dir: &Utf8Path) -> Utf8PathBuf {
+    git::toplevel(project_dir)
+        .zip(project_dir.canonicalize_utf8().ok())
+        .and_then(|(root, dir)| dir.strip_prefix(root).ok().map(Utf8Path::to_path_buf))
+        .unwrap_or_default()
+}
+
+/// Print the patch coverage and the coverage summary table to STDOUT, if enabled.
+fn print_summaries(cli: &Cli, files: &[FileInfo], totals: &schema::Summary) -> Result<()> {
+    if cli.diff_base.is_some() {
+        let patch = files
+            .iter()
+            .map(FileInfo::patch_coverage)
+            .sum::<templates::PatchCoverage>();
+        println!(
+            "patch coverage: {:.2}% ({}/{} changed lines)",
+            patch.percent, patch.covered, patch.count
+        );
+    }
+
+    if let Some(count) = cli.summary {
+        summary::print(
+            std::io::stdout().lock(),
+            files,
+            totals,
+            count,
+            summary::use_color(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Load a previous report to compare against, which is either a summary export from an earlier
+/// run, or a coverage file from `llvm-cov`.
+fn load_baseline(
+    path: &Utf8Path,
+    project_files: &[(Utf8PathBuf, Utf8PathBuf)],
+    remaps: &[cli::PathRemap],
+) -> Result<Baseline> {
+    /// Just the type of the document. Exports of `llvm-cov` always have one, while summaries
+    /// don't, so parsing errors can be reported for the format the file is actually in.
+    #[derive(serde::Deserialize)]
+    struct Document {
+        #[serde(rename = "type")]
+        ty: Option<serde::de::IgnoredAny>,
+    }
+
+    let content =
+        fs::read(path).wrap_err_with(|| format!("failed reading baseline from {path:?}"))?;
+
+    let document = serde_json::from_slice::<Document>(&content)
+        .wrap_err_with(|| format!("failed parsing baseline from {path:?}"))?;
+
+    if document.ty.is_none() {
+        return serde_json::from_slice::<Baseline>(&content)
+            .wrap_err_with(|| format!("failed parsing baseline summary from {path:?}"));
+    }
+
+    let JsonExport {
+        data: [mut export], ..
+    } = JsonExport::from_reader(content.as_slice())
+        .wrap_err_with(|| format!("failed parsing baseline from {path:?}"))?;
+
+    remap_paths(&mut export, remaps);
+    let files = merge_file_info(project_files.to_vec(), &export.files);
+
+    Ok(Baseline::new(&files, &export.totals))
+}
+
+/// Ensure that none of the total coverage metrics dropped by more than the tolerance, compared to
+/// the baseline.
+fn check_regressions(regressions: &[(cli::Metric, f64)], tolerance: f64) -> Result<()> {
+    if regressions.is_empty() {
+        return Ok(());
+    }
+
+    let details = regressions
+        .iter()
+        .map(|(metric, drop)| format!("{metric} dropped by {drop:.2}%"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Err(eyre!(
+        "coverage regressed by more than {tolerance:.2}% compared to the baseline: {details}"
+    ))
+}
+
+/// Load the coverage data from the given file, or STDIN if no file was given.
+fn load_export(input: Option<&Utf8Path>) -> Result<JsonExport> {
+    if let Some(input) = input {
+        let file = BufReader::new(File::open(input)?);
+        JsonExport::from_reader(file)
+            .wrap_err_with(|| format!("failed parsing report data from {input:?}"))
+    } else {
+        let stdin = std::io::stdin().lock();
+        JsonExport::from_reader(stdin).wrap_err("failed parsing report data from STDIN")
+    }
+}
+
+/// Write the HTML report into the output directory.
+///
+/// Source pages that didn't change since the last run are taken from the cache, and the cache is
+/// updated with the new pages afterwards.
+fn write_html(
+    cli: &Cli,
+    output_dir: &Utf8Path,
+    index: &templates::Index<'_>,
+    sources: &[String],
+    functions: &[schema::Function],
+    cache: &Cache,
+) -> Result<()> {
+    let minifier = Minifier::new();
+    let index_path = output_dir.join("index.html");
+    let pages = render_sources(cli, index.files, sources, functions, cache, &minifier)?;
+    let search_index =
+        SearchIndex::new(index.files, sources, functions, !cli.skip_function_coverage)
+            .to_script()?;
+    let hot_lines = index
+        .hot_lines
+        .then(|| {
+            templates::HotLines::new(index.files, sources, cli.hot_lines, cli.single_file).render()
+        })
+        .transpose()?;
+
+    if cli.single_file {
         fs::write(
-            output,
+            &index_path,
             minifier.minify(
-                templates::Source {
-                    title: file.relative_path.as_str(),
-                    base_dir: &"../".repeat(file.relative_path.ancestors().skip(2).count()),
-                    lines: &lines,
-                    info: &file,
-                    coverage_style: cli.coverage_style,
-                    show_instantiations: cli.show_instantiations,
-                    overlay_instantiations: !cli.skip_function_coverage,
+                templates::SingleFile {
+                    title: "Coverage Report",
+                    stylesheet: STYLESHEET,
+                    syntax: cli.theme.as_str(),
+                    index: &index.render()?,
+                    pages: &pages,
+                    search_index: &search_index,
+                    hot_lines: hot_lines.as_deref(),
                 }
-                .render()?
-                .as_bytes(),
+                .render()?,
             ),
         )?;
+    } else {
+        fs::write(output_dir.join("style.css"), STYLESHEET.as_bytes())?;
+        fs::write(output_dir.join("syntax.css"), cli.theme.as_bytes())?;
+        fs::write(output_dir.join(search::FILE_NAME), search_index)?;
+        fs::write(&index_path, minifier.minify(index.render()?))?;
 
-        Ok::<_, Error>(())
-    })?;
+        if let Some(hot_lines) = hot_lines {
+            fs::write(
+                output_dir.join("hot-lines.html"),
+                minifier.minify(hot_lines),
+            )?;
+        }
 
-    if cli.open {
-        open::that(index_path)?;
-    } else {
-        println!(
-            "report generated at {:?}",
-            index_path.strip_prefix(project_dir).unwrap_or(&index_path)
-        );
+        pages.par_iter().try_for_each(|(path, page)| {
+            let output = output_dir.join(path).with_extension("rs.html");
+
+            if let Some(parent) = output.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            fs::write(output, &page.content)
+        })?;
+    }
+
+    Cache::new(
+        pages
+            .into_iter()
+            .map(|(path, page)| (path.to_owned(), page)),
+    )
+    .save(output_dir)
+}
+
+/// Render the source view for each of the files, together with their path relative to the project
+/// root. The pages are minified, unless they're embedded into a single file report.
+///
+/// Pages are only rendered if the cache doesn't contain a page for the same inputs.
+fn render_sources<'a>(
+    cli: &Cli,
+    files: &'a [FileInfo],
+    sources: &[String],
+    functions: &[schema::Function],
+    cache: &Cache,
+    minifier: &Minifier,
+) -> Result<Vec<(&'a Utf8Path, cache::Page)>> {
+    let highlighter = Highlighter::new();
+
+    files
+        .par_iter()
+        .zip(sources)
+        .map(|(file, source)| {
+            let key = cache::page_key(
+                file,
+                source,
+                functions,
+                (
+                    cli.single_file,
+                    cli.no_highlight,
+                    cli.coverage_style,
+                    cli.show_instantiations,
+                    cli.skip_function_coverage,
+                    cli.collapse_covered,
+                ),
+            );
+
+            if let Some(content) = cache.get(&file.relative_path, key) {
+                let content = content.to_owned();
+                return Ok((file.relative_path.as_path(), cache::Page { key, content }));
+            }
+
+            let lines = highlighter.source_to_spans(source, cli.no_highlight)?;
+            let outline = outline::items(file, source, functions, !cli.skip_function_coverage);
+            let page = templates::Source {
+                title: file.relative_path.as_str(),
+                base_dir: &"../".repeat(file.relative_path.ancestors().skip(2).count()),
+                single_file: cli.single_file,
+                lines: &lines,
+                info: file,
+                coverage_style: cli.coverage_style,
+                show_instantiations: cli.show_instantiations,
+                overlay_instantiations: !cli.skip_function_coverage,
+                collapse_context: cli.collapse_covered,
+                outline: &outline,
+            }
+            .render()?;
+
+            let content = if cli.single_file {
+                page
+            } else {
+                String::from_utf8(minifier.minify(page))?
+            };
+
+            Ok((file.relative_path.as_path(), cache::Page { key, content }))
+        })
+        .collect()
+}
+
+/// Print all files as annotated plain text to STDOUT.
+fn print_text(cli: &Cli, files: &[FileInfo], sources: &[String]) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+
+    for (file, source) in files.iter().zip(sources) {
+        text::print(
+            &mut stdout,
+            file,
+            source,
+            !cli.skip_function_coverage,
+            cli.text_context,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_exports(
+    cli: &Cli,
+    manifest_path: Option<&Utf8Path>,
+    output_dir: &Utf8Path,
+    files: &[FileInfo],
+    export: &schema::Export,
+    repo_prefix: &Utf8Path,
+    annotations: &[annotations::Annotation<'_>],
+) -> Result<()> {
+    for format in &cli.export {
+        let output = output_dir.join(format.file_name());
+
+        match format {
+            ExportFormat::Sonarqube => fs::write(output, templates::SonarQube { files }.render()?)?,
+            ExportFormat::Gitlab => fs::write(
+                output,
+                annotations::gitlab_report(repo_prefix, annotations)?,
+            )?,
+            ExportFormat::Sarif => fs::write(
+                output,
+                sarif::report(repo_prefix, files, &export.functions, annotations)?,
+            )?,
+            ExportFormat::Summary => fs::write(
+                output,
+                serde_json::to_string_pretty(&Baseline::new(files, &export.totals))?,
+            )?,
+            ExportFormat::Badge => {
+                let badge = |summary: &schema::Summary| templates::Badge {
+                    label: &cli.badge_label,
+                    percent: cli.badge_metric.percent(summary),
+                };
+
+                fs::write(output, badge(&export.totals).render()?)?;
+
+                if cli.offline {
+                    continue;
+                }
+
+                let packages =
+                    cargo::packages(manifest_path).wrap_err("failed to locate workspace crates")?;
+                let badges_dir = output_dir.join("badges");
+                fs::create_dir_all(&badges_dir)?;
+
+                for (name, summary) in package_summaries(&packages, files) {
+                    fs::write(
+                        badges_dir.join(format!("{name}.svg")),
+                        badge(&summary).render()?,
+                    )?;
+                }
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Sum up the coverage of all files per crate. Files are assigned to the crate with the closest
+/// root directory, so nested crates don't count towards their parent.
+fn package_summaries<'a>(
+    packages: &'a [cargo::Package],
+    files: &[FileInfo],
+) -> Vec<(&'a str, schema::Summary)> {
+    packages
+        .iter()
+        .map(|package| {
+            let summary = files
+                .iter()
+                .filter(|file| {
+                    packages
+                        .iter()
+                        .filter(|p| file.path.starts_with(&p.root))
+                        .max_by_key(|p| p.root.as_str().len())
+                        .is_some_and(|p| p.root == package.root)
+                })
+                .map(|file| &file.summary)
+                .sum();
+
+            (package.name.as_str(), summary)
+        })
+        .collect()
+}
+
 fn collect_project_files(dir: &Utf8Path) -> Result<Vec<(Utf8PathBuf, Utf8PathBuf)>> {
     let mut files = Vec::new();
 
@@ -169,6 +766,260 @@ fn collect_project_files(dir: &Utf8Path) -> Result<Vec<(Utf8PathBuf, Utf8PathBuf
     Ok(files)
 }
 
+/// Collect the source files of the project as they exist at the given git revision.
+fn collect_revision_files(dir: &Utf8Path, rev: &str) -> Result<Vec<(Utf8PathBuf, Utf8PathBuf)>> {
+    Ok(git::source_files(dir, rev)
+        .wrap_err_with(|| format!("failed listing source files at {rev}"))?
+        .into_iter()
+        .map(|relative| (dir.join(&relative), relative))
+        .collect())
+}
+
+/// Apply the path remappings to all file paths of the coverage data, so they can be matched against
+/// the local source files.
+fn remap_paths(export: &mut schema::Export, remaps: &[cli::PathRemap]) {
+    if remaps.is_empty() {
+        return;
+    }
+
+    for file in &mut export.files {
+        cli::PathRemap::apply(remaps, &mut file.filename);
+    }
+
+    for function in &mut export.functions {
+        for filename in &mut function.filenames {
+            cli::PathRemap::apply(remaps, filename);
+        }
+    }
+}
+
+/// Find all files that couldn't be matched between the coverage data and the project.
+fn collect_diagnostics(
+    project_files: &[(Utf8PathBuf, Utf8PathBuf)],
+    coverage: &[schema::File],
+) -> Diagnostics {
+    let mut diagnostics = Diagnostics {
+        missing_sources: coverage
+            .iter()
+            .filter(|info| {
+                !project_files
+                    .iter()
+                    .any(|(path, relative_path)| is_same_file(info, path, relative_path))
+            })
+            .map(|info| info.filename.clone())
+            .collect(),
+        missing_coverage: project_files
+            .iter()
+            .filter(|(path, relative_path)| {
+                !coverage
+                    .iter()
+                    .any(|info| is_same_file(info, path, relative_path))
+            })
+            .map(|(_, relative_path)| relative_path.clone())
+            .collect(),
+        stale_sources: Vec::new(),
+    };
+
+    diagnostics.missing_sources.sort_unstable();
+    diagnostics.missing_coverage.sort_unstable();
+    diagnostics
+}
+
+/// Print a warning for each kind of mismatch between the coverage data and the project.
+fn warn_diagnostics(project_dir: &Utf8Path, diagnostics: &Diagnostics, include_unreached: bool) {
+    if !diagnostics.missing_sources.is_empty() {
+        eprintln!(
+            "warning: {} file(s) of the coverage data didn't match any source file in \
+             {project_dir:?}:",
+            diagnostics.missing_sources.len()
+        );
+        for path in &diagnostics.missing_sources {
+            eprintln!("  {path}");
+        }
+        eprintln!(
+            "hint: pass `--remap-path-prefix` if the coverage data was collected in a different \
+             location"
+        );
+    }
+
+    if !diagnostics.missing_coverage.is_empty() {
+        eprintln!(
+            "warning: {} source file(s) don't have any coverage data:",
+            diagnostics.missing_coverage.len()
+        );
+        for path in &diagnostics.missing_coverage {
+            eprintln!("  {path}");
+        }
+        if !include_unreached {
+            eprintln!(
+                "hint: pass `--include-unreached` to show them as uncovered files in the report"
+            );
+        }
+    }
+
+    for (path, reason) in &diagnostics.stale_sources {
+        eprintln!("warning: source file {path} might not match the coverage data, as {reason}");
+    }
+
+    if !diagnostics.stale_sources.is_empty() {
+        eprintln!("hint: pass `--source-rev` to render the sources from the tested git revision");
+    }
+}
+
+/// Detect source files that likely changed since the coverage data was collected, by comparing
+/// line numbers and modification times, and optionally the git status.
+fn find_stale_sources(
+    cli: &Cli,
+    project_dir: &Utf8Path,
+    files: &[FileInfo],
+    sources: &[String],
+) -> Result<Vec<(Utf8PathBuf, String)>> {
+    // Modification times can only be compared against an input file, and are meaningless for
+    // sources from a git revision.
+    let created = cli
+        .input
+        .as_ref()
+        .filter(|_| cli.source_rev.is_none())
+        .and_then(|input| fs::metadata(input).and_then(|m| m.modified()).ok());
+    let modified = if cli.check_git_status {
+        git::modified_files(project_dir).wrap_err("failed to collect modified files")?
+    } else {
+        HashSet::new()
+    };
+
+    let mut stale = files
+        .par_iter()
+        .zip(sources)
+        .filter_map(|(file, source)| {
+            let mut reasons = Vec::new();
+
+            let line_count = source.lines().count();
+            let last_line = file
+                .covered
+                .keys()
+                .chain(&file.uncovered)
+                .chain(file.branches.keys())
+                .max()
+                .copied()
+                .unwrap_or_default();
+
+            if last_line > line_count {
+                reasons.push(format!(
+                    "it has only {line_count} lines, but the coverage data refers to line \
+                     {last_line}"
+                ));
+            }
+
+            let changed = fs::metadata(&file.path).and_then(|m| m.modified()).ok();
+            if created
+                .zip(changed)
+                .is_some_and(|(created, changed)| changed > created)
+            {
+                reasons.push("it was modified after the coverage data was created".to_owned());
+            }
+
+            if modified.contains(&file.relative_path) {
+                reasons.push("it has uncommitted changes".to_owned());
+            }
+
+            (!reasons.is_empty()).then(|| (file.relative_path.clone(), reasons.join(", and ")))
+        })
+        .collect::<Vec<_>>();
+
+    stale.sort_unstable();
+    Ok(stale)
+}
+
+/// Sort the files by their path, and return their content in the same order. Files without any
+/// content are removed.
+fn sort_files(files: &mut Vec<FileInfo>, mut sources: HashMap<Utf8PathBuf, String>) -> Vec<String> {
+    files.retain(|file| sources.contains_key(&file.relative_path));
+    files.sort_unstable_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    files
+        .iter()
+        .filter_map(|file| sources.remove(&file.relative_path))
+        .collect()
+}
+
+/// Read the content of the source files (relative to the project root), either from the working
+/// tree or the git revision that was selected by the user.
+///
+/// Files that don't exist at the git revision are skipped with a warning, so they don't show up in
+/// the report.
+fn read_sources<'a>(
+    cli: &Cli,
+    project_dir: &Utf8Path,
+    paths: impl Iterator<Item = &'a Utf8Path>,
+) -> Result<HashMap<Utf8PathBuf, String>> {
+    let paths = paths.collect::<Vec<_>>();
+
+    let Some(rev) = &cli.source_rev else {
+        return paths
+            .into_par_iter()
+            .map(|relative_path| {
+                let path = project_dir.join(relative_path);
+                let source = fs::read_to_string(&path)
+                    .wrap_err_with(|| format!("failed reading file contents from {path:?}"))?;
+                Ok((relative_path.to_owned(), source))
+            })
+            .collect();
+    };
+
+    let sources = git::show_files(project_dir, rev, &paths)
+        .wrap_err_with(|| format!("failed reading file contents at {rev}"))?;
+
+    Ok(paths
+        .into_iter()
+        .zip(sources)
+        .filter_map(|(relative_path, source)| {
+            if source.is_none() {
+                eprintln!("warning: source file {relative_path} doesn't exist at {rev}, skipping");
+            }
+            Some((relative_path.to_owned(), source?))
+        })
+        .collect())
+}
+
+/// Create the coverage information for a source file without any coverage data, where all lines
+/// that likely contain code are uncovered.
+fn unreached_file_info(project_dir: &Utf8Path, relative_path: &Utf8Path, source: &str) -> FileInfo {
+    let path = project_dir.join(relative_path);
+
+    let uncovered = source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with("//")
+        })
+        .map(|(i, _)| i + 1)
+        .collect::<HashSet<_>>();
+
+    let mut summary = schema::Summary::default();
+    summary.lines.count = uncovered.len() as u64;
+
+    FileInfo {
+        path,
+        relative_path: relative_path.to_owned(),
+        summary,
+        covered: HashMap::new(),
+        uncovered,
+        called: HashMap::new(),
+        uncalled: HashMap::new(),
+        branches: HashMap::new(),
+        changed: BTreeSet::new(),
+    }
+}
+
+/// Check whether the coverage data of a file belongs to the project file with the given paths.
+fn is_same_file(info: &schema::File, path: &Utf8Path, relative_path: &Utf8Path) -> bool {
+    if info.filename.is_absolute() {
+        info.filename == path
+    } else {
+        info.filename == relative_path
+    }
+}
+
 fn merge_file_info(
     files: Vec<(Utf8PathBuf, Utf8PathBuf)>,
     coverage: &[schema::File],
@@ -176,13 +1027,9 @@ fn merge_file_info(
     files
         .into_par_iter()
         .filter_map(|(path, relative_path)| {
-            let info = coverage.iter().find(|info| {
-                if info.filename.is_absolute() {
-                    info.filename == path
-                } else {
-                    info.filename == relative_path
-                }
-            })?;
+            let info = coverage
+                .iter()
+                .find(|info| is_same_file(info, &path, &relative_path))?;
 
             Some(FileInfo {
                 path,
@@ -198,6 +1045,8 @@ fn merge_file_info(
                     .collect(),
                 called: HashMap::new(),
                 uncalled: HashMap::new(),
+                branches: branches_to_lines(&info.branches),
+                changed: BTreeSet::new(),
             })
         })
         .collect()
@@ -214,6 +1063,17 @@ fn segments_to_ranges(
     })
 }
 
+fn branches_to_lines(branches: &[schema::BranchRegion]) -> HashMap<usize, (u64, u64)> {
+    branches.iter().fold(HashMap::new(), |mut lines, branch| {
+        // Each branch region describes both the `true` and `false` case of a condition.
+        let (count, covered) = lines.entry(branch.line_start as usize).or_default();
+        *count += 2;
+        *covered +=
+            u64::from(branch.execution_count > 0) + u64::from(branch.false_execution_count > 0);
+        lines
+    })
+}
+
 fn merge_function_info(files: &mut Vec<FileInfo>, functions: &[schema::Function]) {
     files.par_iter_mut().for_each(|file| {
         for function in functions.iter().filter(|f| f.filenames[0] == file.path) {
@@ -237,3 +1097,50 @@ fn merge_function_info(files: &mut Vec<FileInfo>, functions: &[schema::Function]
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use camino::Utf8PathBuf;
+    use clap::Parser;
+    use time::OffsetDateTime;
+
+    use crate::{cli::Cli, history, schema};
+
+    #[test]
+    fn prepare_output_dir_keeps_history() {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join(format!("llvm-cov-pretty-history-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+
+        let mut cli = Cli::parse_from(["llvm-cov-pretty", "--no-cache", "--history"]);
+        let run = |cli: &Cli| {
+            super::prepare_output_dir(
+                cli,
+                &dir,
+                &dir,
+                &dir,
+                &schema::Summary::default(),
+                OffsetDateTime::UNIX_EPOCH,
+            )
+            .unwrap()
+            .0
+            .len()
+        };
+
+        assert_eq!(1, run(&cli));
+
+        // Runs without `--history` neither record nor drop entries.
+        cli.history = false;
+        assert_eq!(0, run(&cli));
+        assert_eq!(1, history::load(&dir).unwrap().len());
+
+        cli.history = true;
+        assert_eq!(2, run(&cli));
+        assert_eq!(2, history::load(&dir).unwrap().len());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
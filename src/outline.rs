@@ -0,0 +1,334 @@
+//! Outline of the items in a source file, to evaluate the coverage item by item.
+//!
+//! Functions are taken from the coverage data, as their regions describe exactly which lines belong
+//! to them. Modules, implementation blocks and traits have no regions on their own, so they're
+//! found with a lightweight scan of the source instead. The scan relies on the code being
+//! formatted with `rustfmt`, where a block is closed by a `}` with the same indentation as the
+//! line that opened it.
+
+use std::{cmp::Reverse, collections::BTreeMap, fmt, ops::RangeInclusive};
+
+use crate::{
+    schema,
+    templates::{Coverage, FileInfo},
+};
+
+/// Kind of an [`Item`] in the outline.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ItemKind {
+    Module,
+    Impl,
+    Trait,
+    Function,
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Module => "mod",
+            Self::Impl => "impl",
+            Self::Trait => "trait",
+            Self::Function => "fn",
+        })
+    }
+}
+
+/// A single item of the outline, together with the line coverage of its body.
+#[derive(Debug)]
+pub struct Item {
+    pub kind: ItemKind,
+    /// Display name, like the function name or the header of an implementation block.
+    pub name: String,
+    /// First line of the item, which is used as jump target.
+    pub line: usize,
+    /// Last line of the item.
+    pub end: usize,
+    /// Nesting level, with `0` for top-level items.
+    pub depth: usize,
+    /// Line coverage as `(covered, count)`, for all lines of the item with coverage information.
+    pub lines: (u64, u64),
+}
+
+impl Item {
+    /// Percentage of covered lines, or `None` if the item doesn't contain any executable lines.
+    pub fn percent(&self) -> Option<f64> {
+        (self.lines.1 > 0).then(|| schema::percent(self.lines.0, self.lines.1))
+    }
+}
+
+/// Create the outline of a single file, from its source content and the functions of the report.
+/// Items are sorted by their position in the file.
+pub fn items(
+    info: &FileInfo,
+    source: &str,
+    functions: &[schema::Function],
+    overlay_instantiations: bool,
+) -> Vec<Item> {
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut ranges = blocks(&lines);
+
+    // Generic functions appear once per instantiation, so they're merged by their first line.
+    // Closures are skipped, as they're always part of another function.
+    let mut merged = BTreeMap::<usize, (usize, &str)>::new();
+    for function in functions
+        .iter()
+        .filter(|f| f.filenames[0] == info.path && !f.name.contains("{closure"))
+    {
+        let Some(start) = function.own_regions().map(|r| r.start.0).min() else {
+            continue;
+        };
+        let end = function
+            .own_regions()
+            .map(|r| r.end.0)
+            .max()
+            .unwrap_or(start);
+
+        let entry = merged
+            .entry(start as usize)
+            .or_insert((end as usize, function.name.as_str()));
+        entry.0 = entry.0.max(end as usize);
+    }
+
+    ranges.extend(merged.into_iter().map(|(start, (end, name))| {
+        let name = start
+            .checked_sub(1)
+            .and_then(|index| lines.get(index))
+            .and_then(|line| function_name(line))
+            .unwrap_or_else(|| last_segment(name));
+        (ItemKind::Function, name.to_owned(), start..=end)
+    }));
+    ranges.sort_by_key(|(_, _, range)| (*range.start(), Reverse(*range.end())));
+
+    ranges
+        .iter()
+        .enumerate()
+        .map(|(i, (kind, name, range))| {
+            let depth = ranges[..i]
+                .iter()
+                .filter(|(_, _, parent)| {
+                    parent.contains(range.start()) && parent.contains(range.end())
+                })
+                .count();
+            let lines = range.clone().fold((0, 0), |(covered, count), line| {
+                match info.coverage(line, overlay_instantiations) {
+                    Coverage::Covered(_) => (covered + 1, count + 1),
+                    Coverage::Uncovered(_) => (covered, count + 1),
+                    Coverage::Unknown => (covered, count),
+                }
+            });
+
+            Item {
+                kind: *kind,
+                name: name.clone(),
+                line: *range.start(),
+                end: *range.end(),
+                depth,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Find all module, implementation and trait blocks in the source, as one-based line ranges.
+fn blocks(lines: &[&str]) -> Vec<(ItemKind, String, RangeInclusive<usize>)> {
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let (kind, header) = block_header(line)?;
+            let indent = line.len() - line.trim_start().len();
+
+            // Empty blocks like `impl Trait for Type {}` are closed on the same line.
+            if line.trim_end().ends_with('}') {
+                return Some((kind, header, index + 1..=index + 1));
+            }
+
+            // The header can span several lines (for example with `where` clauses), so the block
+            // ends at the next closing brace with the same indentation.
+            let end = lines[index + 1..].iter().position(|line| {
+                line.len() - line.trim_start().len() == indent && line.trim().starts_with('}')
+            })?;
+
+            Some((kind, header, index + 1..=index + end + 2))
+        })
+        .collect()
+}
+
+/// Parse the kind and display name of a block, if the line opens a module, implementation or trait.
+fn block_header(line: &str) -> Option<(ItemKind, String)> {
+    let line = line.trim();
+    if line.ends_with(';') {
+        return None;
+    }
+
+    let line = line.strip_suffix("{}").unwrap_or(line);
+    let item = strip_visibility(line);
+    let item = item.strip_prefix("unsafe ").unwrap_or(item);
+    let header = line.trim_end_matches('{').trim_end();
+
+    if let Some(name) = item.strip_prefix("mod ") {
+        Some((
+            ItemKind::Module,
+            name.trim_end_matches('{').trim().to_owned(),
+        ))
+    } else if item.starts_with("impl ") || item.starts_with("impl<") {
+        Some((ItemKind::Impl, strip_visibility(header).to_owned()))
+    } else if let Some(name) = item.strip_prefix("trait ") {
+        let end = name
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(name.len());
+        Some((ItemKind::Trait, name[..end].to_owned()))
+    } else {
+        None
+    }
+}
+
+/// Remove a leading visibility modifier like `pub` or `pub(crate)`.
+fn strip_visibility(item: &str) -> &str {
+    let Some(rest) = item.strip_prefix("pub") else {
+        return item;
+    };
+
+    let rest = match rest.strip_prefix('(') {
+        Some(rest) => rest.split_once(')').map_or(rest, |(_, rest)| rest),
+        None => rest,
+    };
+
+    if rest.starts_with(' ') {
+        rest.trim_start()
+    } else {
+        item
+    }
+}
+
+/// Extract the function name from its signature line, like `pub fn name<T>(...)`.
+fn function_name(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("fn ")?;
+    let end = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(rest.len());
+
+    (end > 0).then(|| &rest[..end])
+}
+
+/// Last path segment of a demangled function name, without any generic arguments.
+fn last_segment(name: &str) -> &str {
+    let name = name.split("::<").next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::ItemKind;
+    use crate::{schema, templates::FileInfo};
+
+    #[test]
+    fn block_header() {
+        assert_eq!(
+            Some((ItemKind::Module, "tests".to_owned())),
+            super::block_header("mod tests {")
+        );
+        assert_eq!(None, super::block_header("pub(crate) mod tests;"));
+        assert_eq!(
+            Some((ItemKind::Impl, "impl<'a> Source<'a>".to_owned())),
+            super::block_header("  impl<'a> Source<'a> {")
+        );
+        assert_eq!(
+            Some((
+                ItemKind::Impl,
+                "unsafe impl fmt::Display for Kind".to_owned()
+            )),
+            super::block_header("unsafe impl fmt::Display for Kind {")
+        );
+        assert_eq!(
+            Some((ItemKind::Impl, "impl Error for Value".to_owned())),
+            super::block_header("impl Error for Value {}")
+        );
+        assert_eq!(
+            Some((ItemKind::Trait, "Render".to_owned())),
+            super::block_header("pub(super) trait Render: Sized {")
+        );
+        assert_eq!(None, super::block_header("let module = 5;"));
+    }
+
+    #[test]
+    fn items() {
+        let info = FileInfo {
+            covered: HashMap::from([(6, 1), (7, 1), (8, 1), (11, 1)]),
+            uncovered: HashSet::from([12, 13]),
+            ..FileInfo::fixture("src/file.rs")
+        };
+        let source = "\
+struct Value;
+
+impl Error for Value {}
+
+impl Value {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn get<T>(&self) -> T
+    where
+        T: Default,
+    {
+        T::default()
+    }
+}";
+        let function = |name: &str, start, end, count| schema::Function {
+            name: name.to_owned(),
+            count,
+            regions: vec![schema::Region {
+                start: (start, 5),
+                end: (end, 6),
+                execution_count: count,
+                file_id: 0,
+                expanded_file_id: 0,
+                kind: schema::RegionKind::Code,
+            }],
+            branches: Vec::new(),
+            filenames: ["/home/user/project/src/file.rs".into()],
+        };
+        let mut functions = [
+            function("file::Value::new", 6, 8, 1),
+            function("file::Value::get::<u8>", 11, 15, 1),
+            function("file::Value::get::<u16>", 11, 13, 0),
+            function("file::Value::new::{closure#0}", 7, 7, 1),
+        ];
+        // Expansion of a macro from another file, that must not stretch the function.
+        functions[0].regions.push(schema::Region {
+            start: (30, 1),
+            end: (40, 2),
+            execution_count: 1,
+            file_id: 1,
+            expanded_file_id: 0,
+            kind: schema::RegionKind::Code,
+        });
+
+        let items = super::items(&info, source, &functions, true);
+
+        assert_eq!(
+            vec![
+                (ItemKind::Impl, "impl Error for Value", 3, 3, 0, (0, 0)),
+                (ItemKind::Impl, "impl Value", 5, 16, 0, (4, 6)),
+                (ItemKind::Function, "new", 6, 8, 1, (3, 3)),
+                (ItemKind::Function, "get", 11, 15, 1, (1, 3)),
+            ],
+            items
+                .iter()
+                .map(|item| (
+                    item.kind,
+                    item.name.as_str(),
+                    item.line,
+                    item.end,
+                    item.depth,
+                    item.lines
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(None, items[0].percent());
+        assert_eq!(Some(100.0), items[2].percent());
+    }
+}
@@ -0,0 +1,135 @@
+//! Preparation of the output directory, that the report is written to.
+
+use std::{fs, io::ErrorKind};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::{
+    eyre::{eyre, Result, WrapErr},
+    Help,
+};
+
+/// Name of the marker file, that identifies a directory as created by this program.
+const MARKER: &str = ".llvm-cov-pretty";
+
+/// Create a fresh, empty output directory.
+///
+/// Any previous directory is only deleted, if it was created by an earlier run (identified by the
+/// marker file) or is empty. Otherwise, this function refuses to touch the directory, unless
+/// `force` is set.
+pub fn prepare(dir: &Utf8Path, force: bool) -> Result<()> {
+    match fs::read_dir(dir) {
+        Ok(mut entries) => {
+            if !force && entries.next().is_some() && !dir.join(MARKER).is_file() {
+                return Err(eyre!(
+                    "refusing to delete the output directory {dir:?}, as it wasn't created by a \
+                     previous run"
+                )
+                .suggestion("pick a different output directory, or pass `--force` to delete it"));
+            }
+
+            fs::remove_dir_all(dir)
+                .wrap_err_with(|| format!("failed deleting output directory {dir:?}"))?;
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(e).wrap_err_with(|| format!("failed reading output directory {dir:?}"));
+        }
+    }
+
+    fs::create_dir_all(dir)
+        .wrap_err_with(|| format!("failed creating output directory {dir:?}"))?;
+    fs::write(
+        dir.join(MARKER),
+        concat!(
+            "This directory was created by ",
+            env!("CARGO_PKG_NAME"),
+            " and is deleted on each run.\n"
+        ),
+    )
+    .wrap_err("failed writing output directory marker")
+}
+
+/// Directory next to the output directory, that a new report is written to before it replaces
+/// the current one through [`swap`].
+pub fn staging_dir(dir: &Utf8Path) -> Utf8PathBuf {
+    sibling(dir, "new")
+}
+
+/// Replace the output directory with the report in the staging directory. The previous report is
+/// moved out of the way first, so the output directory is only missing for the brief moment
+/// between two renames, instead of the whole time a report is generated.
+pub fn swap(dir: &Utf8Path, staging: &Utf8Path) -> Result<()> {
+    let old = sibling(dir, "old");
+    if old.exists() {
+        fs::remove_dir_all(&old)
+            .wrap_err_with(|| format!("failed deleting previous report {old:?}"))?;
+    }
+
+    fs::rename(dir, &old).wrap_err_with(|| format!("failed moving away the report in {dir:?}"))?;
+    fs::rename(staging, dir)
+        .wrap_err_with(|| format!("failed moving the new report into {dir:?}"))?;
+    fs::remove_dir_all(&old).wrap_err_with(|| format!("failed deleting previous report {old:?}"))
+}
+
+/// Hidden directory next to the given one, with the suffix appended to its name.
+fn sibling(dir: &Utf8Path, suffix: &str) -> Utf8PathBuf {
+    dir.with_file_name(format!(
+        ".{}.{suffix}",
+        dir.file_name().unwrap_or(env!("CARGO_PKG_NAME"))
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use camino::Utf8PathBuf;
+
+    #[test]
+    fn prepare() {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join(format!("llvm-cov-pretty-output-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+
+        // Missing and empty directories are created.
+        super::prepare(&dir, false).unwrap();
+        fs::write(dir.join("index.html"), "").unwrap();
+
+        // Directories from a previous run are cleared.
+        super::prepare(&dir, false).unwrap();
+        assert!(!dir.join("index.html").exists());
+
+        // Foreign directories are only deleted when forced.
+        fs::remove_file(dir.join(super::MARKER)).unwrap();
+        fs::write(dir.join("important.txt"), "").unwrap();
+        assert!(super::prepare(&dir, false).is_err());
+        assert!(dir.join("important.txt").exists());
+
+        super::prepare(&dir, true).unwrap();
+        assert!(!dir.join("important.txt").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn swap() {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join(format!("llvm-cov-pretty-swap-{}", std::process::id()));
+        let staging = super::staging_dir(&dir);
+
+        super::prepare(&dir, false).unwrap();
+        fs::write(dir.join("index.html"), "old").unwrap();
+        super::prepare(&staging, false).unwrap();
+        fs::write(staging.join("index.html"), "new").unwrap();
+
+        super::swap(&dir, &staging).unwrap();
+
+        assert_eq!("new", fs::read_to_string(dir.join("index.html")).unwrap());
+        assert!(!staging.exists());
+        assert!(!super::sibling(&dir, "old").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+//! Export of uncovered code in the SARIF 2.1.0 format, so it can be triaged like the findings of
+//! static analysis tools.
+//!
+//! See <https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html> for the specification.
+
+use std::collections::BTreeMap;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize;
+
+use crate::{annotations::Annotation, schema, templates::FileInfo};
+
+/// Rule for functions that were never called.
+const RULE_FUNCTION: &str = "uncovered-function";
+/// Rule for ranges of lines that were never executed.
+const RULE_LINES: &str = "uncovered-lines";
+
+#[derive(Serialize)]
+struct Log {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: [Run; 1],
+}
+
+#[derive(Serialize)]
+struct Run {
+    tool: Tool,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: [Rule; 2],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: &'static str,
+    short_description: Message,
+}
+
+#[derive(Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    rule_index: usize,
+    level: &'static str,
+    message: Message,
+    locations: [Location; 1],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactLocation {
+    uri: Utf8PathBuf,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_column: Option<u64>,
+    end_line: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_column: Option<u64>,
+}
+
+/// Create a SARIF log, that contains all uncovered functions and uncovered line ranges as results.
+///
+/// Functions are only reported if none of their instantiations was called. Like for the GitHub
+/// annotations, paths are relative to the repository root, which is what code scanning tools
+/// resolve them against.
+pub fn report(
+    repo_prefix: &Utf8Path,
+    files: &[FileInfo],
+    functions: &[schema::Function],
+    annotations: &[Annotation<'_>],
+) -> serde_json::Result<String> {
+    let results = uncovered_functions(files, functions)
+        .into_iter()
+        .map(|(path, function)| {
+            let start = function.own_regions().map(|r| r.start).min();
+            let end = function.own_regions().map(|r| r.end).max();
+
+            result(
+                RULE_FUNCTION,
+                format!("Function `{}` is never called", function.name),
+                repo_prefix.join(path),
+                Region {
+                    start_line: start.map_or(1, |s| s.0),
+                    start_column: start.map(|s| s.1),
+                    end_line: end.map_or(1, |e| e.0),
+                    end_column: end.map(|e| e.1),
+                },
+            )
+        })
+        .chain(annotations.iter().map(|annotation| {
+            result(
+                RULE_LINES,
+                "Lines are never executed".to_owned(),
+                repo_prefix.join(annotation.path),
+                Region {
+                    start_line: *annotation.lines.start() as u64,
+                    start_column: None,
+                    end_line: *annotation.lines.end() as u64,
+                    end_column: None,
+                },
+            )
+        }))
+        .collect();
+
+    serde_json::to_string_pretty(&Log {
+        schema: "https://json.schemastore.org/sarif-2.1.0.json",
+        version: "2.1.0",
+        runs: [Run {
+            tool: Tool {
+                driver: Driver {
+                    name: env!("CARGO_PKG_NAME"),
+                    version: env!("CARGO_PKG_VERSION"),
+                    information_uri: env!("CARGO_PKG_REPOSITORY"),
+                    rules: [
+                        Rule {
+                            id: RULE_FUNCTION,
+                            short_description: Message {
+                                text: "Function is never called".to_owned(),
+                            },
+                        },
+                        Rule {
+                            id: RULE_LINES,
+                            short_description: Message {
+                                text: "Lines are never executed".to_owned(),
+                            },
+                        },
+                    ],
+                },
+            },
+            results,
+        }],
+    })
+}
+
+/// Find all functions, where none of the instantiations at the same source location was called,
+/// together with the file path relative to the project root.
+fn uncovered_functions<'a>(
+    files: &'a [FileInfo],
+    functions: &'a [schema::Function],
+) -> Vec<(&'a Utf8Path, &'a schema::Function)> {
+    let mut locations = BTreeMap::<_, (&schema::Function, bool)>::new();
+
+    for function in functions {
+        let Some(file) = files.iter().find(|f| f.path == function.filenames[0]) else {
+            continue;
+        };
+        let Some(start) = function.own_regions().map(|r| r.start).min() else {
+            continue;
+        };
+
+        let (_, called) = locations
+            .entry((file.relative_path.as_path(), start))
+            .or_insert((function, false));
+        *called |= function.count > 0;
+    }
+
+    locations
+        .into_iter()
+        .filter(|(_, (_, called))| !called)
+        .map(|((path, _), (function, _))| (path, function))
+        .collect()
+}
+
+/// Create a single result entry for the given rule.
+fn result(
+    rule_id: &'static str,
+    message: String,
+    path: Utf8PathBuf,
+    region: Region,
+) -> SarifResult {
+    SarifResult {
+        rule_id,
+        rule_index: usize::from(rule_id == RULE_LINES),
+        level: "warning",
+        message: Message { text: message },
+        locations: [Location {
+            physical_location: PhysicalLocation {
+                artifact_location: ArtifactLocation { uri: path },
+                region,
+            },
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8Path;
+    use serde_json::{json, Value};
+
+    use crate::{
+        annotations::Annotation,
+        schema::{Function, Region, RegionKind},
+        templates::FileInfo,
+    };
+
+    fn function(name: &str, count: u64, line: u64) -> Function {
+        Function {
+            name: name.to_owned(),
+            count,
+            regions: vec![Region {
+                start: (line, 1),
+                end: (line + 2, 2),
+                execution_count: count,
+                file_id: 0,
+                expanded_file_id: 0,
+                kind: RegionKind::Code,
+            }],
+            branches: vec![],
+            filenames: ["/home/user/project/src/file.rs".into()],
+        }
+    }
+
+    #[test]
+    fn report() {
+        let files = [FileInfo::fixture("src/file.rs")];
+        let mut unused = function("file::unused", 0, 5);
+        // Expansion of a macro from another file, that must not stretch the function's location.
+        unused.regions.push(Region {
+            start: (100, 1),
+            end: (120, 2),
+            execution_count: 0,
+            file_id: 1,
+            expanded_file_id: 0,
+            kind: RegionKind::Code,
+        });
+        let functions = [
+            function("file::used::<u8>", 1, 1),
+            function("file::used::<u16>", 0, 1),
+            unused,
+        ];
+        let annotations = [Annotation {
+            path: &files[0].relative_path,
+            lines: 6..=7,
+        }];
+
+        let output = super::report(
+            Utf8Path::new("crates/app"),
+            &files,
+            &functions,
+            &annotations,
+        )
+        .unwrap();
+        let output = serde_json::from_str::<Value>(&output).unwrap();
+
+        assert_eq!(
+            json!([
+                {
+                    "ruleId": "uncovered-function",
+                    "ruleIndex": 0,
+                    "level": "warning",
+                    "message": { "text": "Function `file::unused` is never called" },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": "crates/app/src/file.rs" },
+                            "region": { "startLine": 5, "startColumn": 1, "endLine": 7, "endColumn": 2 }
+                        }
+                    }]
+                },
+                {
+                    "ruleId": "uncovered-lines",
+                    "ruleIndex": 1,
+                    "level": "warning",
+                    "message": { "text": "Lines are never executed" },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": "crates/app/src/file.rs" },
+                            "region": { "startLine": 6, "endLine": 7 }
+                        }
+                    }]
+                }
+            ]),
+            output["runs"][0]["results"]
+        );
+    }
+}
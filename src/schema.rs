@@ -5,7 +5,7 @@ use std::io::Read;
 use camino::Utf8PathBuf;
 use color_eyre::{eyre, Help, Result};
 use semver::Version;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Root structure of the JSON output that is generated by `cargo-llvm-cov`/`llvm-cov`.
 #[derive(Deserialize)]
@@ -76,10 +76,9 @@ pub struct File {
     pub segments: Vec<Segment>,
     /// Coverage for code branches (like if statements, I assume).
     ///
-    /// **Note:** Appears to be unused for Rust projects (as branch coverage support is still
-    /// experimental).
+    /// **Note:** Only filled for Rust projects if branch coverage is enabled, which is still
+    /// experimental.
     #[serde(default)]
-    #[allow(dead_code)]
     pub branches: Vec<BranchRegion>,
     /// Coverage for code expansions caused by generics or templates. For example, structs with
     /// generic arguments that get expanded into concrete versions for specific types.
@@ -142,7 +141,6 @@ pub struct Function {
     #[serde(with = "demangle")]
     pub name: String,
     /// Total invocation count for the function.
-    #[allow(dead_code)]
     pub count: u64,
     /// Source code regions where the function call happened (or is missing).
     pub regions: Vec<Region>,
@@ -156,6 +154,14 @@ pub struct Function {
     pub filenames: [Utf8PathBuf; 1],
 }
 
+impl Function {
+    /// Regions that are located in the file the function is defined in. This excludes regions of
+    /// macro expansions, which can point to entirely unrelated lines of other files.
+    pub fn own_regions(&self) -> impl Iterator<Item = &Region> {
+        self.regions.iter().filter(|region| region.file_id == 0)
+    }
+}
+
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub struct Region {
     /// Start position of the region (line and column).
@@ -164,8 +170,8 @@ pub struct Region {
     pub end: (u64, u64),
     /// How many times the function was executed in this region.
     pub execution_count: u64,
-    /// Unique identifier for the file, but appears to be always `0`.
-    #[allow(dead_code)]
+    /// Index of the file that contains the region. Anything other than `0` points to the file of
+    /// a macro expansion, instead of the file the function is defined in.
     pub file_id: u64,
     /// Unique identifier for the expanded file, but appears to be always `0`.
     #[allow(dead_code)]
@@ -269,21 +275,19 @@ pub struct Expansion {
     pub branches: Vec<BranchRegion>,
 }
 
-#[derive(Clone, Deserialize)]
-#[cfg_attr(test, derive(Debug, Default, PartialEq))]
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
 pub struct Summary {
     pub lines: CoverageCounts,
     pub functions: CoverageCounts,
-    #[allow(dead_code)]
     pub instantiations: CoverageCounts,
     pub regions: CoverageCounts2,
-    #[allow(dead_code)]
     pub branches: CoverageCounts2,
 }
 
 /// Sum of coverage counts as part of the [`Summary`].
-#[derive(Clone, Deserialize)]
-#[cfg_attr(test, derive(Debug, Default, PartialEq))]
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
 pub struct CoverageCounts {
     pub count: u64,
     pub covered: u64,
@@ -292,16 +296,62 @@ pub struct CoverageCounts {
 
 /// Sum of coverage counts like [`CoverageCounts`], but including the extra [`Self::notcovered`]
 /// field, which is only present for [`Summary::regions`] and [`Summary::branches`].
-#[derive(Clone, Deserialize)]
-#[cfg_attr(test, derive(Debug, Default, PartialEq))]
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
 pub struct CoverageCounts2 {
     pub count: u64,
     pub covered: u64,
-    #[allow(dead_code)]
     pub notcovered: u64,
     pub percent: f64,
 }
 
+impl<'a> std::iter::Sum<&'a Summary> for Summary {
+    fn sum<I: Iterator<Item = &'a Summary>>(iter: I) -> Self {
+        let mut total = iter.fold(Self::default(), |mut acc, summary| {
+            acc.lines.add(&summary.lines);
+            acc.functions.add(&summary.functions);
+            acc.instantiations.add(&summary.instantiations);
+            acc.regions.add(&summary.regions);
+            acc.branches.add(&summary.branches);
+            acc
+        });
+
+        total.lines.percent = percent(total.lines.covered, total.lines.count);
+        total.functions.percent = percent(total.functions.covered, total.functions.count);
+        total.instantiations.percent =
+            percent(total.instantiations.covered, total.instantiations.count);
+        total.regions.percent = percent(total.regions.covered, total.regions.count);
+        total.branches.percent = percent(total.branches.covered, total.branches.count);
+        total
+    }
+}
+
+impl CoverageCounts {
+    fn add(&mut self, other: &Self) {
+        self.count += other.count;
+        self.covered += other.covered;
+    }
+}
+
+impl CoverageCounts2 {
+    fn add(&mut self, other: &Self) {
+        self.count += other.count;
+        self.covered += other.covered;
+        self.notcovered += other.notcovered;
+    }
+}
+
+/// Calculate the coverage percentage the same way `llvm-cov` does, which is `0` in case there is
+/// nothing to cover.
+#[allow(clippy::cast_precision_loss)]
+pub fn percent(covered: u64, count: u64) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        covered as f64 / count as f64 * 100.0
+    }
+}
+
 /// Contextual information from `cargo-llvm-cov`.
 #[derive(Clone, Deserialize)]
 #[cfg_attr(test, derive(Debug, PartialEq))]
@@ -353,7 +403,10 @@ mod tests {
     use semver::Version;
     use serde_json::json;
 
-    use super::{Export, File, Function, JsonExport, Region, RegionKind, Segment, Summary};
+    use super::{
+        CoverageCounts, CoverageCounts2, Export, File, Function, JsonExport, Region, RegionKind,
+        Segment, Summary,
+    };
     use crate::schema::CargoLlvmCov;
 
     #[test]
@@ -597,4 +650,38 @@ mod tests {
 
         assert_eq!(expect, serde_json::from_value::<JsonExport>(input).unwrap());
     }
+
+    #[test]
+    fn sum_summaries() {
+        let summary = Summary {
+            lines: CoverageCounts {
+                count: 4,
+                covered: 1,
+                percent: 25.0,
+            },
+            regions: CoverageCounts2 {
+                count: 2,
+                covered: 1,
+                notcovered: 1,
+                percent: 50.0,
+            },
+            ..Summary::default()
+        };
+        let expect = Summary {
+            lines: CoverageCounts {
+                count: 8,
+                covered: 2,
+                percent: 25.0,
+            },
+            regions: CoverageCounts2 {
+                count: 4,
+                covered: 2,
+                notcovered: 2,
+                percent: 50.0,
+            },
+            ..Summary::default()
+        };
+
+        assert_eq!(expect, [&summary, &summary].into_iter().sum());
+    }
 }
@@ -0,0 +1,328 @@
+//! Search index over the function names and identifiers of all source files.
+//!
+//! The index is written as a script next to the report, that assigns it to a global variable. This
+//! way it can be loaded from the static files without a server, as browsers don't allow fetching
+//! other local files.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use camino::Utf8Path;
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    schema,
+    templates::{Coverage, FileInfo},
+};
+
+/// Name of the search index script inside the output directory.
+pub const FILE_NAME: &str = "search-index.js";
+
+/// Keywords of the Rust language, which are excluded from the identifiers.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+/// Coverage state of a line, in the compact form that is stored in the index. The ordering makes
+/// merging the states of function instantiations prefer the covered one.
+#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(into = "u8")]
+enum State {
+    Unknown = 0,
+    Covered = 1,
+    Uncovered = 2,
+}
+
+impl From<State> for u8 {
+    fn from(value: State) -> Self {
+        value as u8
+    }
+}
+
+impl From<Coverage> for State {
+    fn from(value: Coverage) -> Self {
+        match value {
+            Coverage::Covered(_) => Self::Covered,
+            Coverage::Uncovered(_) => Self::Uncovered,
+            Coverage::Unknown => Self::Unknown,
+        }
+    }
+}
+
+/// Searchable names of the whole report. Files are referenced by their position in
+/// [`Self::files`], to keep the index small.
+#[derive(Serialize)]
+pub struct SearchIndex<'a> {
+    /// Paths of all source files, relative to the project root.
+    files: Vec<&'a Utf8Path>,
+    /// All functions as `[name, file, line, state]`.
+    functions: Vec<(&'a str, usize, u64, State)>,
+    /// All identifiers together with their occurrences as `[file, line, state]`.
+    identifiers: BTreeMap<&'a str, BTreeSet<(usize, usize, State)>>,
+}
+
+impl<'a> SearchIndex<'a> {
+    /// Create the index from the files of the report and their source content.
+    pub fn new(
+        files: &'a [FileInfo],
+        sources: &'a [String],
+        functions: &'a [schema::Function],
+        overlay_instantiations: bool,
+    ) -> Self {
+        let mut index = Self {
+            files: files
+                .iter()
+                .map(|file| file.relative_path.as_path())
+                .collect(),
+            functions: Vec::new(),
+            identifiers: BTreeMap::new(),
+        };
+        // Generic functions appear once per instantiation. They're merged into a single entry, that
+        // counts as covered if any of the instantiations was called.
+        let mut merged_functions = BTreeMap::<_, State>::new();
+
+        for (file_index, (file, source)) in files.iter().zip(sources).enumerate() {
+            for function in functions.iter().filter(|f| f.filenames[0] == file.path) {
+                let Some(line) = function.own_regions().map(|r| r.start.0).min() else {
+                    continue;
+                };
+                let state = if function.count > 0 {
+                    State::Covered
+                } else {
+                    State::Uncovered
+                };
+
+                merged_functions
+                    .entry((function.name.as_str(), file_index, line))
+                    .and_modify(|merged| *merged = (*merged).min(state))
+                    .or_insert(state);
+            }
+
+            for (line_number, identifier) in identifiers(source) {
+                let state = State::from(file.coverage(line_number, overlay_instantiations));
+
+                index.identifiers.entry(identifier).or_default().insert((
+                    file_index,
+                    line_number,
+                    state,
+                ));
+            }
+        }
+
+        index.functions = merged_functions
+            .into_iter()
+            .map(|((name, file, line), state)| (name, file, line, state))
+            .collect();
+
+        index
+    }
+
+    /// Serialize the index into a script, that assigns it to the global `searchIndex` variable.
+    pub fn to_script(&self) -> Result<String> {
+        // Escape closing tags, so the script can be embedded into an HTML document as well.
+        let json = serde_json::to_string(self)?.replace("</", r"<\/");
+        Ok(format!("window.searchIndex = {json};"))
+    }
+}
+
+/// Split the source code into its identifiers together with their line number, skipping keywords
+/// and single characters. Words in comments and literals aren't identifiers, and are skipped too.
+fn identifiers(source: &str) -> impl Iterator<Item = (usize, &str)> {
+    code_parts(source)
+        .into_iter()
+        .flat_map(|(line_number, part)| {
+            part.split('\n')
+                .enumerate()
+                .flat_map(move |(offset, line)| {
+                    line.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .filter(|word| {
+                            word.len() > 1
+                                && !word.starts_with(|c: char| c.is_ascii_digit())
+                                && !KEYWORDS.contains(word)
+                        })
+                        .map(move |word| (line_number + offset, word))
+                })
+        })
+}
+
+/// Split the source code into the parts between comments, string literals and character literals,
+/// together with the line number each part starts at.
+fn code_parts(source: &str) -> Vec<(usize, &str)> {
+    let bytes = source.as_bytes();
+    let mut parts = Vec::new();
+    let (mut start, mut line_number, mut i) = (0, 1, 0);
+
+    while i < bytes.len() {
+        let Some(end) = literal_end(source, i) else {
+            i += 1;
+            continue;
+        };
+
+        parts.push((line_number, &source[start..i]));
+        line_number += source[start..end].matches('\n').count();
+        start = end;
+        i = end;
+    }
+
+    parts.push((line_number, &source[start..]));
+    parts
+}
+
+/// If a comment or literal starts at the given position, find the position right after it.
+fn literal_end(source: &str, start: usize) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let after = |offset: usize| bytes.get(start + offset).copied();
+    let find = |from: usize, pattern: &str| {
+        source[from.min(bytes.len())..]
+            .find(pattern)
+            .map_or(bytes.len(), |pos| from + pos + pattern.len())
+    };
+
+    match bytes[start] {
+        b'/' if after(1) == Some(b'/') => Some(
+            source[start..]
+                .find('\n')
+                .map_or(bytes.len(), |pos| start + pos),
+        ),
+        b'/' if after(1) == Some(b'*') => {
+            // Block comments can be nested.
+            let mut depth = 0;
+            let mut i = start;
+            while i < bytes.len() {
+                match (bytes[i], bytes.get(i + 1)) {
+                    (b'/', Some(b'*')) => depth += 1,
+                    (b'*', Some(b'/')) => depth -= 1,
+                    _ => {
+                        i += 1;
+                        continue;
+                    }
+                }
+                i += 2;
+                if depth == 0 {
+                    break;
+                }
+            }
+            Some(i.min(bytes.len()))
+        }
+        b'"' => {
+            let mut i = start + 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            Some((i + 1).min(bytes.len()))
+        }
+        b'r' if start == 0 || !is_identifier(bytes[start - 1]) || bytes[start - 1] == b'b' => {
+            let hashes = bytes[start + 1..]
+                .iter()
+                .take_while(|&&b| b == b'#')
+                .count();
+            (after(hashes + 1) == Some(b'"'))
+                .then(|| find(start + hashes + 2, &format!("\"{}", "#".repeat(hashes))))
+        }
+        b'\'' if after(1) == Some(b'\\') => Some(find(start + 3, "'")),
+        b'\'' => {
+            // A character literal, unless it's the name of a lifetime.
+            let len = source[start + 1..].chars().next()?.len_utf8();
+            (after(len + 1) == Some(b'\'')).then_some(start + len + 2)
+        }
+        _ => None,
+    }
+}
+
+/// Whether the byte can be part of an identifier.
+fn is_identifier(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::SearchIndex;
+    use crate::{schema, templates::FileInfo};
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(
+            vec![
+                (1, "parse_header"),
+                (1, "input"),
+                (1, "str"),
+                (1, "Result"),
+                (1, "Header"),
+                (1, "u8"),
+            ],
+            super::identifiers("pub fn parse_header(input: &str) -> Result<Header<'_>, u8> { 0 }")
+                .collect::<Vec<_>>()
+        );
+
+        let source = r##"/// Doc comment
+let name = "string // literal"; // comment
+/* block /* nested */ comment
+*/ let quote = '"'; let raw = r#"raw "string" literal"#;
+let escaped = "\" not_string";
+let lifetime: &'static str = '\'';
+"##;
+        assert_eq!(
+            vec![
+                (2, "name"),
+                (4, "quote"),
+                (4, "raw"),
+                (5, "escaped"),
+                (6, "lifetime"),
+                (6, "str"),
+            ],
+            super::identifiers(source).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn build_index() {
+        let files = [FileInfo {
+            covered: HashMap::from([(1, 1)]),
+            uncovered: HashSet::from([2]),
+            ..FileInfo::fixture("src/file.rs")
+        }];
+        let sources = ["fn parse_header() {\n    parse_header()\n}".to_owned()];
+        let function = |count| schema::Function {
+            name: "project::parse_header".to_owned(),
+            count,
+            regions: vec![schema::Region {
+                start: (1, 1),
+                end: (3, 2),
+                execution_count: count,
+                file_id: 0,
+                expanded_file_id: 0,
+                kind: schema::RegionKind::Code,
+            }],
+            branches: Vec::new(),
+            filenames: ["/home/user/project/src/file.rs".into()],
+        };
+        let mut uncalled = function(0);
+        // Expansion of a macro from another file, that must not change the function's location.
+        uncalled.regions.insert(
+            0,
+            schema::Region {
+                start: (100, 1),
+                end: (120, 2),
+                execution_count: 0,
+                file_id: 1,
+                expanded_file_id: 0,
+                kind: schema::RegionKind::Code,
+            },
+        );
+        let functions = [function(1), uncalled];
+
+        let script = SearchIndex::new(&files, &sources, &functions, true)
+            .to_script()
+            .unwrap();
+
+        assert_eq!(
+            r#"window.searchIndex = {"files":["src/file.rs"],"functions":[["project::parse_header",0,1,1]],"identifiers":{"parse_header":[[0,1,1],[0,2,2]]}};"#,
+            script
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Local HTTP server for the report, that regenerates it whenever the coverage data or any of the
+//! source files change, and reloads all open browser tabs afterwards.
+//!
+//! The server is intentionally minimal, as it only serves static files from the output directory
+//! to the local machine. Reloads are pushed to the browser through [server-sent events].
+//!
+//! [server-sent events]: https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{Result, WrapErr};
+
+/// Path of the event stream, that notifies browsers about changes.
+const RELOAD_PATH: &str = "/__livereload";
+/// Script that is injected into each HTML page, to reload it on changes.
+const RELOAD_SCRIPT: &str =
+    r#"<script>new EventSource("/__livereload").onmessage = () => location.reload();</script>"#;
+/// Interval in which the watched files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Open event streams of all connected browsers.
+type Clients = Arc<Mutex<Vec<TcpStream>>>;
+
+/// Serve the report in the output directory on the given address, and keep it up-to-date.
+///
+/// The `watched` function lists the files that the report depends on. It's called on every poll,
+/// so that newly created files are picked up as well. Whenever any of them change (or files are
+/// added or removed), the `regenerate` function is called.
+pub fn run(
+    address: SocketAddr,
+    output_dir: &Utf8Path,
+    mut watched: impl FnMut() -> Result<Vec<Utf8PathBuf>>,
+    mut regenerate: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    let mut last = modification_times(watched()?);
+
+    let listener =
+        TcpListener::bind(address).wrap_err_with(|| format!("failed binding to {address}"))?;
+    let clients = Clients::default();
+
+    println!("serving report at http://{}", listener.local_addr()?);
+
+    {
+        let output_dir = output_dir.to_owned();
+        let clients = Arc::clone(&clients);
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let output_dir = output_dir.clone();
+                let clients = Arc::clone(&clients);
+
+                thread::spawn(move || {
+                    if let Err(e) = handle(stream, &output_dir, &clients) {
+                        eprintln!("failed handling request: {e}");
+                    }
+                });
+            }
+        });
+    }
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let current = match watched() {
+            Ok(files) => modification_times(files),
+            Err(e) => {
+                eprintln!("{e:?}");
+                continue;
+            }
+        };
+        if current == last {
+            continue;
+        }
+
+        // Changes that happen during the regeneration are picked up by the next poll, as the
+        // times from before the regeneration are kept.
+        match regenerate() {
+            Ok(()) => {
+                reload(&clients);
+                println!("report regenerated");
+            }
+            Err(e) => eprintln!("{e:?}"),
+        }
+
+        last = current;
+    }
+}
+
+/// Collect the last modification time of each file. Files that can't be accessed (for example
+/// because they're in the middle of being re-written) are recorded without a time.
+fn modification_times(files: Vec<Utf8PathBuf>) -> HashMap<Utf8PathBuf, Option<SystemTime>> {
+    files
+        .into_iter()
+        .map(|file| {
+            let modified = fs::metadata(&file).and_then(|m| m.modified()).ok();
+            (file, modified)
+        })
+        .collect()
+}
+
+/// Notify all connected browsers to reload the page, and drop any disconnected ones.
+fn reload(clients: &Clients) {
+    let mut clients = clients
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    clients.retain_mut(|stream| {
+        stream
+            .write_all(b"data: reload\n\n")
+            .and_then(|()| stream.flush())
+            .is_ok()
+    });
+}
+
+/// Handle a single HTTP request, by either serving a file from the output directory or registering
+/// the connection as event stream for reloads.
+fn handle(mut stream: TcpStream, output_dir: &Utf8Path, clients: &Clients) -> io::Result<()> {
+    let mut request_line = String::new();
+    {
+        let mut reader = BufReader::new(&stream);
+        reader.read_line(&mut request_line)?;
+
+        // Skip the headers, as none of them are relevant for serving static files.
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond(&mut stream, "400 Bad Request", "text/plain", b"bad request");
+    };
+
+    if method != "GET" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"method not allowed",
+        );
+    }
+
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+
+    if path == RELOAD_PATH {
+        stream.write_all(
+            b"HTTP/1.1 200 OK\r\n\
+              Content-Type: text/event-stream\r\n\
+              Cache-Control: no-cache\r\n\
+              Connection: keep-alive\r\n\r\n",
+        )?;
+        stream.flush()?;
+
+        clients
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(stream);
+        return Ok(());
+    }
+
+    let Some(file) = resolve(output_dir, path) else {
+        return respond(&mut stream, "404 Not Found", "text/plain", b"not found");
+    };
+
+    match fs::read(&file) {
+        Ok(mut content) => {
+            let content_type = content_type(&file);
+            if content_type.starts_with("text/html") {
+                content.extend_from_slice(RELOAD_SCRIPT.as_bytes());
+            }
+
+            respond(&mut stream, "200 OK", content_type, &content)
+        }
+        Err(_) => respond(&mut stream, "404 Not Found", "text/plain", b"not found"),
+    }
+}
+
+/// Write a complete response with the given status and body.
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: \
+         {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Map the request path to a file inside the output directory. Paths that would escape the output
+/// directory are rejected, and directories are mapped to their `index.html` file.
+fn resolve(output_dir: &Utf8Path, path: &str) -> Option<Utf8PathBuf> {
+    let path = percent_decode(path)?;
+    let relative = Utf8Path::new(path.trim_start_matches('/'));
+
+    if !relative
+        .components()
+        .all(|c| matches!(c, Utf8Component::Normal(_) | Utf8Component::CurDir))
+    {
+        return None;
+    }
+
+    let file = output_dir.join(relative);
+    Some(if file.is_dir() {
+        file.join("index.html")
+    } else {
+        file
+    })
+}
+
+/// Decode `%XX` escape sequences in the request path.
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+/// Guess the content type of file, based on its extension.
+fn content_type(file: &Utf8Path) -> &'static str {
+    match file.extension() {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json" | "sarif") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("xml") => "application/xml",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::{Utf8Path, Utf8PathBuf};
+
+    #[test]
+    fn resolve() {
+        let dir = Utf8Path::new("/nonexistent/report");
+
+        assert_eq!(
+            Some(Utf8PathBuf::from("/nonexistent/report/src/my file.rs.html")),
+            super::resolve(dir, "/src/my%20file.rs.html")
+        );
+        assert_eq!(None, super::resolve(dir, "/../secret.txt"));
+        assert_eq!(None, super::resolve(dir, "/src/%2E%2E/%2E%2E/secret.txt"));
+        assert_eq!(None, super::resolve(dir, "/%zz"));
+    }
+
+    #[test]
+    fn content_type() {
+        assert_eq!(
+            "text/html; charset=utf-8",
+            super::content_type(Utf8Path::new("index.html"))
+        );
+        assert_eq!(
+            "image/svg+xml",
+            super::content_type(Utf8Path::new("badge.svg"))
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Printing of a short coverage summary to the terminal.
+
+use std::io::{self, IsTerminal, Write};
+
+use crate::{
+    schema::Summary,
+    templates::{CoverageLevel, FileInfo},
+};
+
+/// Width of a single coverage cell, which is enough to fit values like `100.00% (12345/12345)`.
+const CELL_WIDTH: usize = 22;
+
+/// Decide whether the summary should be colored, which is the case if STDOUT is a terminal and the
+/// user didn't opt out through the `NO_COLOR` environment variable (see <https://no-color.org>).
+pub fn use_color() -> bool {
+    io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+/// Print a table with the total coverage and the `count` files that have the lowest line coverage.
+pub fn print(
+    mut out: impl Write,
+    files: &[FileInfo],
+    totals: &Summary,
+    count: usize,
+    color: bool,
+) -> io::Result<()> {
+    let mut least = files.iter().collect::<Vec<_>>();
+    least.sort_by(|a, b| {
+        a.summary
+            .lines
+            .percent
+            .total_cmp(&b.summary.lines.percent)
+            .then_with(|| b.uncovered.len().cmp(&a.uncovered.len()))
+    });
+    least.truncate(count);
+
+    let width = least
+        .iter()
+        .map(|file| file.relative_path.as_str().len())
+        .chain(["Filename".len()])
+        .max()
+        .unwrap_or_default();
+
+    writeln!(
+        out,
+        "{:width$}  {:>CELL_WIDTH$}  {:>CELL_WIDTH$}  {:>CELL_WIDTH$}",
+        "Filename", "Lines", "Functions", "Regions"
+    )?;
+
+    for file in least {
+        print_row(
+            &mut out,
+            file.relative_path.as_str(),
+            &file.summary,
+            width,
+            color,
+        )?;
+    }
+
+    writeln!(out, "{}", "-".repeat(width + (CELL_WIDTH + 2) * 3))?;
+    print_row(&mut out, "Total", totals, width, color)
+}
+
+/// Print a single table row, with the percentages colored according to their coverage level.
+fn print_row(
+    out: &mut impl Write,
+    name: &str,
+    summary: &Summary,
+    width: usize,
+    color: bool,
+) -> io::Result<()> {
+    write!(out, "{name:width$}")?;
+
+    for (covered, count, percent) in [
+        (
+            summary.lines.covered,
+            summary.lines.count,
+            summary.lines.percent,
+        ),
+        (
+            summary.functions.covered,
+            summary.functions.count,
+            summary.functions.percent,
+        ),
+        (
+            summary.regions.covered,
+            summary.regions.count,
+            summary.regions.percent,
+        ),
+    ] {
+        let cell = format!("{percent:.2}% ({covered}/{count})");
+
+        if color {
+            let code = match CoverageLevel::new(percent) {
+                CoverageLevel::VeryHigh => "32",
+                CoverageLevel::High => "33",
+                CoverageLevel::Medium => "38;5;208",
+                CoverageLevel::Low => "31",
+            };
+            write!(out, "  \x1b[{code}m{cell:>CELL_WIDTH$}\x1b[0m")?;
+        } else {
+            write!(out, "  {cell:>CELL_WIDTH$}")?;
+        }
+    }
+
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::{
+        schema::{CoverageCounts, Summary},
+        templates::FileInfo,
+    };
+
+    #[allow(clippy::cast_precision_loss)]
+    fn file(name: &str, covered: u64) -> FileInfo {
+        FileInfo {
+            summary: Summary {
+                lines: CoverageCounts {
+                    count: 4,
+                    covered,
+                    percent: covered as f64 * 25.0,
+                },
+                ..Summary::default()
+            },
+            ..FileInfo::fixture(name)
+        }
+    }
+
+    #[test]
+    fn print_summary() {
+        let files = [
+            file("src/a.rs", 4),
+            file("src/b.rs", 1),
+            file("src/c.rs", 2),
+        ];
+        let totals = files.iter().map(|file| &file.summary).sum();
+
+        let mut output = Vec::new();
+        super::print(&mut output, &files, &totals, 2, false).unwrap();
+
+        let expect = indoc! {"
+            Filename                   Lines               Functions                 Regions
+            src/b.rs            25.00% (1/4)             0.00% (0/0)             0.00% (0/0)
+            src/c.rs            50.00% (2/4)             0.00% (0/0)             0.00% (0/0)
+            --------------------------------------------------------------------------------
+            Total              58.33% (7/12)             0.00% (0/0)             0.00% (0/0)
+        "};
+
+        assert_eq!(expect, String::from_utf8(output).unwrap());
+    }
+}
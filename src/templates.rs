@@ -1,12 +1,21 @@
 #![allow(clippy::trivially_copy_pass_by_ref, clippy::unnecessary_wraps)]
 
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ops::RangeInclusive,
+};
 
 use askama::Template;
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use time::OffsetDateTime;
 
-use crate::{cli::CoverageStyle, schema};
+use crate::{
+    annotations,
+    baseline::{Baseline, Delta},
+    cache,
+    cli::{CoverageStyle, Metric},
+    history, outline, schema, text,
+};
 
 /// Global constant instance with this project's info, so it doesn't have to be included as part of
 /// each template struct.
@@ -27,6 +36,26 @@ struct Project {
     version: &'static str,
 }
 
+/// Mismatches between the files in the coverage data and the source files of the project.
+#[derive(Default)]
+pub struct Diagnostics {
+    /// Files of the coverage data, that don't exist in the project.
+    pub missing_sources: Vec<Utf8PathBuf>,
+    /// Source files of the project (relative to its root), that don't appear in the coverage data.
+    pub missing_coverage: Vec<Utf8PathBuf>,
+    /// Source files (relative to the project root), that likely changed since the coverage data
+    /// was collected, together with the reasons for that assumption.
+    pub stale_sources: Vec<(Utf8PathBuf, String)>,
+}
+
+impl Diagnostics {
+    pub fn is_empty(&self) -> bool {
+        self.missing_sources.is_empty()
+            && self.missing_coverage.is_empty()
+            && self.stale_sources.is_empty()
+    }
+}
+
 /// Location and coverage information of a single file.
 pub struct FileInfo {
     /// Absolute path to the file.
@@ -43,6 +72,122 @@ pub struct FileInfo {
     pub called: HashMap<usize, Vec<(String, u64)>>,
     /// Set of uninstantiated function calls.
     pub uncalled: HashMap<usize, BTreeSet<String>>,
+    /// Mapping from source lines to the amount of branches and how many of them were covered.
+    pub branches: HashMap<usize, (u64, u64)>,
+    /// Set of lines that changed since the diff base, if one was given.
+    pub changed: BTreeSet<usize>,
+}
+
+impl FileInfo {
+    /// Get the coverage for a single line.
+    ///
+    /// If `overlay_instantiations` is set, lines that contain uninstantiated function calls are
+    /// considered uncovered, even if they were executed.
+    pub fn coverage(&self, line: usize, overlay_instantiations: bool) -> Coverage {
+        self.covered
+            .get(&line)
+            .copied()
+            .map(|count| {
+                // Even though the line is covered, we might have a part of it that is not covered.
+                // We keep the original coverage count but still mark it as uncovered.
+                if overlay_instantiations && self.uncalled.contains_key(&line) {
+                    Coverage::Uncovered(count)
+                } else {
+                    Coverage::Covered(count)
+                }
+            })
+            .or_else(|| self.uncovered.get(&line).map(|_| Coverage::Uncovered(0)))
+            .unwrap_or(Coverage::Unknown)
+    }
+
+    /// Set of lines that are fully uncovered, meaning no part of them was executed.
+    pub fn uncovered_lines(&self) -> BTreeSet<usize> {
+        self.uncovered
+            .iter()
+            .filter(|line| !self.covered.contains_key(line))
+            .copied()
+            .collect()
+    }
+
+    /// Coverage of the lines that changed since the diff base.
+    pub fn patch_coverage(&self) -> PatchCoverage {
+        let (count, covered) = self
+            .changed
+            .iter()
+            .filter(|line| self.covered.contains_key(line) || self.uncovered.contains(line))
+            .fold((0, 0), |(count, covered), line| {
+                (
+                    count + 1,
+                    covered + u64::from(self.covered.contains_key(line)),
+                )
+            });
+
+        PatchCoverage {
+            count,
+            covered,
+            percent: schema::percent(covered, count),
+        }
+    }
+
+    /// List of all lines that contain executable code, with their coverage state.
+    fn lines_to_cover(&self) -> Vec<LineToCover> {
+        self.covered
+            .keys()
+            .chain(&self.uncovered)
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|number| LineToCover {
+                number,
+                covered: self.covered.contains_key(&number),
+                branches: self.branches.get(&number).copied(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl FileInfo {
+    /// Create a file of the `/home/user/project` test project without any coverage information,
+    /// as base for test fixtures.
+    pub fn fixture(relative_path: &str) -> Self {
+        Self {
+            path: Utf8PathBuf::from(format!("/home/user/project/{relative_path}")),
+            relative_path: Utf8PathBuf::from(relative_path),
+            summary: schema::Summary::default(),
+            covered: HashMap::default(),
+            uncovered: HashSet::default(),
+            called: HashMap::default(),
+            uncalled: HashMap::default(),
+            branches: HashMap::default(),
+            changed: BTreeSet::default(),
+        }
+    }
+}
+
+/// Coverage of changed lines, also known as patch coverage.
+#[derive(Clone, Copy, Default)]
+pub struct PatchCoverage {
+    /// Amount of changed lines that contain executable code.
+    pub count: u64,
+    /// Amount of changed lines that were executed.
+    pub covered: u64,
+    /// Percentage of covered lines, or zero if no executable lines changed.
+    pub percent: f64,
+}
+
+impl std::iter::Sum for PatchCoverage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        let (count, covered) = iter.fold((0, 0), |(count, covered), patch| {
+            (count + patch.count, covered + patch.covered)
+        });
+
+        Self {
+            count,
+            covered,
+            percent: schema::percent(covered, count),
+        }
+    }
 }
 
 #[derive(Template)]
@@ -50,9 +195,155 @@ pub struct FileInfo {
 pub struct Index<'a> {
     pub title: &'a str,
     pub base_dir: &'a str,
+    pub single_file: bool,
     pub generated: OffsetDateTime,
     pub files: &'a [FileInfo],
     pub totals: &'a schema::Summary,
+    /// The git revision that changes are compared against, if any.
+    pub diff_base: Option<&'a str>,
+    /// A previous report to compare the coverage against, if any.
+    pub baseline: Option<&'a Baseline>,
+    /// Total coverage of previous runs, including the current one, from oldest to newest.
+    pub history: &'a [history::Entry],
+    /// Files that couldn't be matched between the coverage data and the project.
+    pub diagnostics: &'a Diagnostics,
+    /// Whether the page with the most executed lines was created.
+    pub hot_lines: bool,
+}
+
+impl<'a> Index<'a> {
+    fn patch_coverage(&self) -> PatchCoverage {
+        self.files.iter().map(FileInfo::patch_coverage).sum()
+    }
+
+    fn total_delta(&self) -> Option<Delta> {
+        self.baseline
+            .map(|baseline| baseline.total_delta(self.totals))
+    }
+
+    fn file_delta(&self, file: &FileInfo) -> Option<Delta> {
+        self.baseline.and_then(|baseline| baseline.file_delta(file))
+    }
+
+    /// All files that have lines, which are uncovered now but weren't in the baseline, together
+    /// with those lines grouped into ranges.
+    fn newly_uncovered(&self) -> Vec<(&'a FileInfo, Vec<RangeInclusive<usize>>)> {
+        let Some(baseline) = self.baseline else {
+            return Vec::new();
+        };
+
+        self.files
+            .iter()
+            .filter_map(|file| {
+                let lines = baseline.newly_uncovered(file);
+                (!lines.is_empty()).then(|| (file, annotations::line_ranges(lines)))
+            })
+            .collect()
+    }
+
+    /// Coordinates for each of the metrics in the trend chart, where each history entry is
+    /// spread evenly on the horizontal axis.
+    fn trend(&self) -> Vec<TrendLine<'a>> {
+        #[allow(clippy::cast_precision_loss)]
+        let step =
+            (TREND_WIDTH - 2.0 * TREND_PADDING) / self.history.len().saturating_sub(1) as f64;
+        let history = self.history;
+
+        [Metric::Lines, Metric::Functions, Metric::Regions]
+            .into_iter()
+            .map(|metric| TrendLine {
+                metric,
+                points: history
+                    .iter()
+                    .enumerate()
+                    .map(|(i, entry)| {
+                        let percent = metric.percent(&entry.totals);
+                        #[allow(clippy::cast_precision_loss)]
+                        let x = if history.len() == 1 {
+                            TREND_WIDTH / 2.0
+                        } else {
+                            TREND_PADDING + i as f64 * step
+                        };
+
+                        TrendPoint {
+                            x,
+                            y: filters::trend_y(&percent).unwrap_or_default(),
+                            percent,
+                            entry,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Link to a single line in the source view of a file.
+    fn line_link(&self, file: &FileInfo, line: &usize) -> String {
+        if self.single_file {
+            format!("#{}:L{line}", file.relative_path)
+        } else {
+            format!("./{}.html#L{line}", file.relative_path)
+        }
+    }
+}
+
+#[cfg(test)]
+impl<'a> Index<'a> {
+    /// Create an index page of the given files, with all optional sections disabled, as base for
+    /// test fixtures.
+    pub fn fixture(files: &'a [FileInfo], totals: &'a schema::Summary) -> Self {
+        static DIAGNOSTICS: Diagnostics = Diagnostics {
+            missing_sources: Vec::new(),
+            missing_coverage: Vec::new(),
+            stale_sources: Vec::new(),
+        };
+
+        Self {
+            title: "",
+            base_dir: "",
+            single_file: false,
+            generated: OffsetDateTime::UNIX_EPOCH,
+            files,
+            totals,
+            diff_base: None,
+            baseline: None,
+            history: &[],
+            diagnostics: &DIAGNOSTICS,
+            hot_lines: false,
+        }
+    }
+}
+
+/// Width of the trend chart in SVG units.
+const TREND_WIDTH: f64 = 800.0;
+/// Height of the trend chart in SVG units.
+const TREND_HEIGHT: f64 = 200.0;
+/// Space around the plotted area of the trend chart, so points at the edges are fully visible.
+const TREND_PADDING: f64 = 10.0;
+
+/// Progression of a single coverage metric over all runs in the history.
+struct TrendLine<'a> {
+    metric: Metric,
+    points: Vec<TrendPoint<'a>>,
+}
+
+impl TrendLine<'_> {
+    /// Coordinates of all points, in the format of the SVG `points` attribute.
+    fn polyline(&self) -> String {
+        self.points
+            .iter()
+            .map(|point| format!("{:.1},{:.1}", point.x, point.y))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// A single data point of the trend chart.
+struct TrendPoint<'a> {
+    x: f64,
+    y: f64,
+    percent: f64,
+    entry: &'a history::Entry,
 }
 
 #[derive(Template)]
@@ -60,13 +351,23 @@ pub struct Index<'a> {
 pub struct Source<'a> {
     pub title: &'a str,
     pub base_dir: &'a str,
+    pub single_file: bool,
     pub lines: &'a [String],
     pub info: &'a FileInfo,
     pub coverage_style: CoverageStyle,
     pub show_instantiations: bool,
     pub overlay_instantiations: bool,
+    /// Fold runs of covered and non-executable lines, keeping the given amount of context lines
+    /// around uncovered lines.
+    pub collapse_context: Option<usize>,
+    /// Modules, implementation blocks and functions of the file, shown in the sidebar.
+    pub outline: &'a [outline::Item],
 }
 
+/// Minimum amount of lines that are folded into a placeholder. Shorter runs are always shown, as
+/// the placeholder itself takes up a line already.
+const MIN_FOLDED_LINES: usize = 4;
+
 impl<'a> Source<'a> {
     fn get_first(&self) -> Option<usize> {
         (1..=self.lines.len())
@@ -74,29 +375,276 @@ impl<'a> Source<'a> {
     }
 
     fn get_coverage(&self, index: &usize) -> Coverage {
+        self.info.coverage(*index, self.overlay_instantiations)
+    }
+
+    /// Heat of a covered line for the heatmap, between `0.0` (not executed) and `1.0` (the most
+    /// executed line of the file). The count is scaled logarithmically, as a few lines (like the
+    /// body of loops) usually have a much higher count than the rest.
+    ///
+    /// The highest count is taken from [`Self::max_count`], so it's only determined once per page.
+    #[allow(clippy::cast_precision_loss)]
+    fn heat(&self, line: &usize, max_count: &u64) -> f64 {
+        let count = self.info.covered.get(line).copied().unwrap_or_default();
+
+        if count == 0 {
+            0.0
+        } else {
+            ((count + 1) as f64).ln() / ((max_count + 1) as f64).ln()
+        }
+    }
+
+    /// Highest execution count of any line in the file.
+    fn max_count(&self) -> u64 {
         self.info
             .covered
-            .get(index)
+            .values()
+            .max()
             .copied()
-            .map(|count| {
-                // Even though the line is covered, we might have a part of it that is not covered.
-                // We keep the original coverage count but still mark it as uncovered.
-                if self.overlay_instantiations && self.info.uncalled.contains_key(index) {
-                    Coverage::Uncovered(count)
-                } else {
-                    Coverage::Covered(count)
-                }
-            })
-            .or_else(|| {
-                self.info
-                    .uncovered
-                    .get(index)
-                    .map(|_| Coverage::Uncovered(0))
+            .unwrap_or_default()
+    }
+
+    /// Functions (or rather their instantiations) that executed the line, together with their
+    /// execution count. Sorted by the count, highest first.
+    fn line_calls(&self, line: &usize) -> Vec<(&str, u64)> {
+        let Some(calls) = self.info.called.get(line) else {
+            return Vec::new();
+        };
+
+        // A function can have several regions on the same line, so only keep the highest count.
+        let mut merged = HashMap::<&str, u64>::new();
+        for (name, count) in calls {
+            let entry = merged.entry(name.as_str()).or_default();
+            *entry = (*entry).max(*count);
+        }
+
+        let mut calls = merged.into_iter().collect::<Vec<_>>();
+        calls.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        calls
+    }
+
+    /// All blocks of consecutive uncovered lines, for navigating between them.
+    fn uncovered_blocks(&self) -> Vec<UncoveredBlock> {
+        let total = self.lines.len() as u64;
+
+        annotations::line_ranges(
+            (1..=self.lines.len())
+                .filter(|line| matches!(self.get_coverage(line), Coverage::Uncovered(_))),
+        )
+        .into_iter()
+        .map(|range| {
+            let (start, end) = range.into_inner();
+            UncoveredBlock {
+                start,
+                end,
+                top: schema::percent(start as u64 - 1, total),
+                height: schema::percent((end - start + 1) as u64, total),
+            }
+        })
+        .collect()
+    }
+
+    /// Runs of lines that are folded away. These are the gaps between the uncovered lines and their
+    /// context.
+    fn folds(&self) -> Folds {
+        let Some(context) = self.collapse_context else {
+            return Folds::default();
+        };
+
+        let coverage = (1..=self.lines.len())
+            .map(|line| self.get_coverage(&line))
+            .collect::<Vec<_>>();
+        let hunks = text::uncovered_hunks(&coverage, context)
+            .into_iter()
+            .map(|(start, end)| (start + 1, end + 1));
+
+        let mut folds = BTreeMap::new();
+        let mut next = 1;
+
+        for (start, end) in hunks.chain([(self.lines.len() + 1, 0)]) {
+            if start - next >= MIN_FOLDED_LINES {
+                folds.insert(next, start - 1);
+            }
+            next = end + 1;
+        }
+
+        Folds(folds)
+    }
+
+    /// Anchor name of a single line. In single file mode, it's prefixed with the file path, as all
+    /// source files are part of the same document.
+    fn line_anchor(&self, line: &usize) -> String {
+        if self.single_file {
+            format!("{}:L{line}", self.info.relative_path)
+        } else {
+            format!("L{line}")
+        }
+    }
+}
+
+/// Folded line ranges of a [`Source`] page, as start line mapped to the (inclusive) end line.
+#[derive(Default)]
+struct Folds(BTreeMap<usize, usize>);
+
+impl Folds {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Amount of folded lines, if a fold starts at the given line.
+    fn starting_at(&self, line: &usize) -> Option<usize> {
+        self.0.get(line).map(|end| end - line + 1)
+    }
+
+    /// Whether the line is part of any of the folds.
+    fn contains(&self, line: &usize) -> bool {
+        self.0
+            .range(..=line)
+            .next_back()
+            .is_some_and(|(_, end)| line <= end)
+    }
+}
+
+/// Page with the most executed lines of the whole project.
+#[derive(Template)]
+#[template(path = "hot_lines.html")]
+pub struct HotLines<'a> {
+    pub title: &'a str,
+    pub base_dir: &'a str,
+    pub single_file: bool,
+    pub lines: Vec<HotLine<'a>>,
+}
+
+/// A single line on the [`HotLines`] page.
+pub struct HotLine<'a> {
+    pub file: &'a FileInfo,
+    pub line: usize,
+    pub count: u64,
+    /// Source code of the line, without surrounding whitespace.
+    pub source: &'a str,
+}
+
+impl<'a> HotLines<'a> {
+    /// Collect the lines with the highest execution count over all files, given together with
+    /// their source content.
+    pub fn new(
+        files: &'a [FileInfo],
+        sources: &'a [String],
+        limit: usize,
+        single_file: bool,
+    ) -> Self {
+        let mut lines = files
+            .iter()
+            .zip(sources)
+            .flat_map(|(file, source)| {
+                let source = source.lines().collect::<Vec<_>>();
+                file.covered
+                    .iter()
+                    .filter(|(_, &count)| count > 0)
+                    .map(move |(&line, &count)| HotLine {
+                        file,
+                        line,
+                        count,
+                        source: source
+                            .get(line.wrapping_sub(1))
+                            .map_or("", |source| source.trim()),
+                    })
             })
-            .unwrap_or(Coverage::Unknown)
+            .collect::<Vec<_>>();
+
+        lines.sort_unstable_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.file.relative_path.cmp(&b.file.relative_path))
+                .then(a.line.cmp(&b.line))
+        });
+        lines.truncate(limit);
+
+        Self {
+            title: "Hottest lines",
+            base_dir: "./",
+            single_file,
+            lines,
+        }
+    }
+
+    /// Link to the line in the source view of its file.
+    fn line_link(&self, line: &HotLine<'_>) -> String {
+        if self.single_file {
+            format!("#{}:L{}", line.file.relative_path, line.line)
+        } else {
+            format!("./{}.html#L{}", line.file.relative_path, line.line)
+        }
+    }
+}
+
+/// A block of consecutive uncovered lines in a source file.
+struct UncoveredBlock {
+    start: usize,
+    end: usize,
+    /// Offset of the block from the start of the file, in percent of the file length.
+    top: f64,
+    /// Size of the block, in percent of the file length.
+    height: f64,
+}
+
+/// A self-contained report, that combines the index and all source files into a single HTML
+/// document, including the stylesheets.
+#[derive(Template)]
+#[template(path = "single.html")]
+pub struct SingleFile<'a> {
+    pub title: &'a str,
+    pub stylesheet: &'a str,
+    pub syntax: &'a str,
+    /// Content of the index page.
+    pub index: &'a str,
+    /// Relative file paths and their source page.
+    pub pages: &'a [(&'a Utf8Path, cache::Page)],
+    /// Script with the search index, see [`crate::search`].
+    pub search_index: &'a str,
+    /// Content of the page with the most executed lines, if it was created.
+    pub hot_lines: Option<&'a str>,
+}
+
+#[derive(Template)]
+#[template(path = "sonarqube.xml")]
+pub struct SonarQube<'a> {
+    pub files: &'a [FileInfo],
+}
+
+/// A single executable line, in the way `SonarQube` expects it in its generic coverage format.
+struct LineToCover {
+    /// Line number, starting at 1.
+    number: usize,
+    /// Whether the line was executed at least once.
+    covered: bool,
+    /// Amount of branches on this line and how many of them were covered, if any.
+    branches: Option<(u64, u64)>,
+}
+
+#[derive(Template)]
+#[template(path = "badge.svg")]
+pub struct Badge<'a> {
+    pub label: &'a str,
+    pub percent: f64,
+}
+
+impl Badge<'_> {
+    fn label_width(&self) -> usize {
+        text_width(self.label)
+    }
+
+    fn value_width(&self) -> usize {
+        text_width(&format!("{:.1}%", self.percent))
     }
 }
 
+/// Rough estimation of the width that the given text takes up in the badge, including some
+/// padding. The exact value depends on the font that is available for rendering.
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
 /// The coverage inforamation for a single line of code.
 #[derive(Clone, Copy)]
 pub enum Coverage {
@@ -124,10 +672,22 @@ pub enum CoverageLevel {
     Low,
 }
 
+impl CoverageLevel {
+    /// Categorize the given coverage percentage.
+    pub fn new(percent: f64) -> Self {
+        match percent {
+            v if v > 90.0 => Self::VeryHigh,
+            v if v > 75.0 => Self::High,
+            v if v > 50.0 => Self::Medium,
+            _ => Self::Low,
+        }
+    }
+}
+
 mod filters {
     use time::{format_description::FormatItem, macros::format_description, OffsetDateTime};
 
-    use super::CoverageLevel;
+    use super::{CoverageLevel, TREND_HEIGHT, TREND_PADDING};
 
     pub fn format_datetime(value: &OffsetDateTime) -> Result<String, askama::Error> {
         static FORMAT: &[FormatItem<'_>] =
@@ -138,44 +698,196 @@ mod filters {
     }
 
     pub fn coverage_level(value: &f64) -> Result<CoverageLevel, askama::Error> {
-        Ok(match *value {
-            v if v > 90.0 => CoverageLevel::VeryHigh,
-            v if v > 75.0 => CoverageLevel::High,
-            v if v > 50.0 => CoverageLevel::Medium,
-            _ => CoverageLevel::Low,
-        })
+        Ok(CoverageLevel::new(*value))
+    }
+
+    /// Vertical position of a coverage percentage in the trend chart.
+    pub fn trend_y(value: &f64) -> Result<f64, askama::Error> {
+        Ok(TREND_PADDING + (100.0 - value) / 100.0 * (TREND_HEIGHT - 2.0 * TREND_PADDING))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::{HashMap, HashSet};
+    use std::collections::{BTreeSet, HashMap, HashSet};
 
     use askama::Template;
     use camino::Utf8PathBuf;
+    use indoc::indoc;
     use time::OffsetDateTime;
 
-    use super::{schema, CoverageStyle, FileInfo};
+    use super::{schema, CoverageStyle, Diagnostics, FileInfo};
+    use crate::{baseline::Baseline, history};
 
     #[test]
     fn render_index() {
-        super::Index {
-            title: "",
-            base_dir: "",
-            generated: OffsetDateTime::UNIX_EPOCH,
+        super::Index::fixture(
+            &[FileInfo::fixture("src/file.rs")],
+            &schema::Summary::default(),
+        )
+        .render()
+        .unwrap();
+    }
+
+    #[test]
+    fn render_patch_coverage() {
+        let files = [FileInfo {
+            covered: HashMap::from([(1, 1)]),
+            uncovered: HashSet::from([2]),
+            changed: BTreeSet::from([1, 2]),
+            ..FileInfo::fixture("src/file.rs")
+        }];
+
+        let output = super::Index {
+            diff_base: Some("main"),
+            ..super::Index::fixture(&files, &schema::Summary::default())
+        }
+        .render()
+        .unwrap();
+
+        assert!(output.contains("Changed files (since <code>main</code>)"));
+        assert!(output.contains("50.00 %"));
+    }
+
+    #[test]
+    fn render_baseline() {
+        let files = [FileInfo {
+            uncovered: HashSet::from([2, 3]),
+            ..FileInfo::fixture("src/file.rs")
+        }];
+        let baseline = Baseline::new(&files[..0], &schema::Summary::default());
+
+        let output = super::Index {
+            baseline: Some(&baseline),
+            ..super::Index::fixture(&files, &schema::Summary::default())
+        }
+        .render()
+        .unwrap();
+
+        assert!(output.contains("Newly uncovered lines"));
+        assert!(output.contains(r#"href="./src/file.rs.html#L2""#));
+    }
+
+    #[test]
+    fn render_history() {
+        let history = [
+            history::Entry::new(OffsetDateTime::UNIX_EPOCH, None, schema::Summary::default()),
+            history::Entry::new(
+                OffsetDateTime::UNIX_EPOCH,
+                Some("0123456789".to_owned()),
+                schema::Summary::default(),
+            ),
+        ];
+
+        let output = super::Index {
+            history: &history,
+            ..super::Index::fixture(&[], &schema::Summary::default())
+        }
+        .render()
+        .unwrap();
+
+        assert!(
+            output.contains(r#"<polyline class="trend-lines" points="10.0,190.0 790.0,190.0"/>"#)
+        );
+    }
+
+    #[test]
+    fn render_diagnostics() {
+        let output = super::Index {
+            diagnostics: &Diagnostics {
+                missing_sources: vec![Utf8PathBuf::from("/build/src/file.rs")],
+                missing_coverage: Vec::new(),
+                stale_sources: vec![(
+                    Utf8PathBuf::from("src/file.rs"),
+                    "it has uncommitted changes".to_owned(),
+                )],
+            },
+            ..super::Index::fixture(&[], &schema::Summary::default())
+        }
+        .render()
+        .unwrap();
+
+        assert!(output.contains("<code>/build/src/file.rs</code>"));
+        assert!(output.contains("<code>src/file.rs</code>: it has uncommitted changes"));
+    }
+
+    #[test]
+    fn render_sortable_headers() {
+        let output = super::Index::fixture(
+            &[FileInfo::fixture("src/file.rs")],
+            &schema::Summary::default(),
+        )
+        .render()
+        .unwrap();
+
+        assert!(output.contains(r#"id="index-files""#));
+        assert!(output.contains(r#"data-lines-uncovered="0""#));
+        assert!(output.contains(r#"data-sort="functionsUncovered""#));
+    }
+
+    #[test]
+    fn render_hot_lines_link() {
+        let output = super::Index {
+            hot_lines: true,
+            ..super::Index::fixture(&[], &schema::Summary::default())
+        }
+        .render()
+        .unwrap();
+
+        assert!(output.contains(r#"<a href="./hot-lines.html">"#));
+    }
+
+    #[test]
+    fn patch_coverage() {
+        let info = FileInfo {
+            covered: HashMap::from([(1, 1), (2, 3)]),
+            uncovered: HashSet::from([3, 4]),
+            changed: BTreeSet::from([2, 3, 5]),
+            ..FileInfo::fixture("src/file.rs")
+        };
+
+        let patch = info.patch_coverage();
+
+        assert_eq!((2, 1), (patch.count, patch.covered));
+        assert!((patch.percent - 50.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn render_sonarqube() {
+        let output = super::SonarQube {
             files: &[FileInfo {
-                path: Utf8PathBuf::from("/home/user/project/src/file.rs"),
-                relative_path: Utf8PathBuf::from("src/file.rs"),
-                summary: schema::Summary::default(),
-                covered: HashMap::default(),
-                uncovered: HashSet::default(),
-                called: HashMap::default(),
-                uncalled: HashMap::default(),
+                covered: HashMap::from([(1, 5), (2, 1)]),
+                uncovered: HashSet::from([3]),
+                branches: HashMap::from([(2, (2, 1))]),
+                ..FileInfo::fixture("src/file.rs")
             }],
-            totals: &schema::Summary::default(),
         }
         .render()
         .unwrap();
+        let expect = indoc! {r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <coverage version="1">
+              <file path="src/file.rs">
+                <lineToCover lineNumber="1" covered="true"/>
+                <lineToCover lineNumber="2" covered="true" branchesToCover="2" coveredBranches="1"/>
+                <lineToCover lineNumber="3" covered="false"/>
+              </file>
+            </coverage>"#};
+
+        assert_eq!(expect, output);
+    }
+
+    #[test]
+    fn render_badge() {
+        let output = super::Badge {
+            label: "coverage",
+            percent: 87.54,
+        }
+        .render()
+        .unwrap();
+
+        assert!(output.contains("<title>coverage: 87.5%</title>"));
+        assert!(output.contains("fill=\"#eab308\""));
     }
 
     #[test]
@@ -183,21 +895,169 @@ mod tests {
         super::Source {
             title: "",
             base_dir: "",
+            single_file: false,
             lines: &[String::from("test")],
-            info: &FileInfo {
-                path: Utf8PathBuf::from("/home/user/project/src/file.rs"),
-                relative_path: Utf8PathBuf::from("src/file.rs"),
-                summary: schema::Summary::default(),
-                covered: HashMap::default(),
-                uncovered: HashSet::default(),
-                called: HashMap::default(),
-                uncalled: HashMap::default(),
-            },
+            info: &FileInfo::fixture("src/file.rs"),
             coverage_style: CoverageStyle::Line,
             show_instantiations: true,
             overlay_instantiations: true,
+            collapse_context: None,
+            outline: &[],
         }
         .render()
         .unwrap();
     }
+
+    #[test]
+    fn uncovered_blocks() {
+        let info = FileInfo {
+            covered: HashMap::from([(1, 1), (4, 2)]),
+            uncovered: HashSet::from([2, 3, 4, 8]),
+            ..FileInfo::fixture("src/file.rs")
+        };
+        let source = super::Source {
+            title: "",
+            base_dir: "",
+            single_file: false,
+            lines: &vec![String::new(); 10],
+            info: &info,
+            coverage_style: CoverageStyle::Line,
+            show_instantiations: true,
+            overlay_instantiations: true,
+            collapse_context: None,
+            outline: &[],
+        };
+
+        let blocks = source.uncovered_blocks();
+
+        assert_eq!(
+            vec![(2, 3, 10.0, 20.0), (8, 8, 70.0, 10.0)],
+            blocks
+                .iter()
+                .map(|block| (block.start, block.end, block.top, block.height))
+                .collect::<Vec<_>>()
+        );
+
+        let output = source.render().unwrap();
+        assert!(output.contains(r##"<a href="#L2" data-anchor="L2""##));
+    }
+
+    #[test]
+    fn folds() {
+        let info = FileInfo {
+            covered: HashMap::from([(1, 1), (5, 1), (9, 1)]),
+            uncovered: HashSet::from([6]),
+            ..FileInfo::fixture("src/file.rs")
+        };
+        let mut source = super::Source {
+            title: "",
+            base_dir: "",
+            single_file: false,
+            lines: &vec![String::new(); 12],
+            info: &info,
+            coverage_style: CoverageStyle::Line,
+            show_instantiations: true,
+            overlay_instantiations: true,
+            collapse_context: Some(1),
+            outline: &[],
+        };
+
+        let folds = source.folds();
+        assert_eq!(
+            vec![(1, 4), (8, 12)],
+            folds.0.into_iter().collect::<Vec<_>>()
+        );
+
+        let output = source.render().unwrap();
+        assert!(output.contains("4 covered lines"));
+        assert!(output.contains("5 covered lines"));
+
+        source.collapse_context = Some(2);
+        assert_eq!(
+            vec![(9, 12)],
+            source.folds().0.into_iter().collect::<Vec<_>>()
+        );
+
+        source.collapse_context = None;
+        assert!(source.folds().is_empty());
+    }
+
+    #[test]
+    fn hot_lines() {
+        let file = |name: &str, covered| FileInfo {
+            covered,
+            uncovered: HashSet::from([3]),
+            ..FileInfo::fixture(&format!("src/{name}"))
+        };
+        let files = [
+            file("a.rs", HashMap::from([(1, 5), (2, 500)])),
+            file("b.rs", HashMap::from([(1, 50), (2, 5), (3, 0)])),
+        ];
+        let sources = [
+            "fn a() {\n    loop {}\n}".to_owned(),
+            "fn b() {\n    b()\n}".to_owned(),
+        ];
+
+        let page = super::HotLines::new(&files, &sources, 3, false);
+
+        assert_eq!(
+            vec![
+                ("src/a.rs", 2, 500, "loop {}"),
+                ("src/b.rs", 1, 50, "fn b() {"),
+                ("src/a.rs", 1, 5, "fn a() {"),
+            ],
+            page.lines
+                .iter()
+                .map(|line| (
+                    line.file.relative_path.as_str(),
+                    line.line,
+                    line.count,
+                    line.source
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        let output = page.render().unwrap();
+        assert!(output.contains(r#"<a href="./src/a.rs.html#L2">"#));
+    }
+
+    #[test]
+    fn line_calls() {
+        let info = FileInfo {
+            covered: HashMap::from([(1, 7)]),
+            called: HashMap::from([(
+                1,
+                vec![
+                    ("parse::<u8>".to_owned(), 2),
+                    ("parse::<u16>".to_owned(), 5),
+                    ("parse::<u8>".to_owned(), 3),
+                ],
+            )]),
+            ..FileInfo::fixture("src/file.rs")
+        };
+        let source = super::Source {
+            title: "",
+            base_dir: "",
+            single_file: false,
+            lines: &[String::from("parse()")],
+            info: &info,
+            coverage_style: CoverageStyle::Line,
+            show_instantiations: true,
+            overlay_instantiations: true,
+            collapse_context: None,
+            outline: &[],
+        };
+
+        assert_eq!(
+            vec![("parse::<u16>", 5), ("parse::<u8>", 3)],
+            source.line_calls(&1)
+        );
+        assert!(source.line_calls(&2).is_empty());
+        assert_eq!(7, source.max_count());
+        assert!((source.heat(&1, &7) - 1.0).abs() < f64::EPSILON);
+        assert!(source.heat(&2, &7).abs() < f64::EPSILON);
+
+        let output = source.render().unwrap();
+        assert!(output.contains("<p><span>parse::&lt;u16&gt;</span><span>5</span></p>"));
+    }
 }
@@ -0,0 +1,163 @@
+//! Rendering of source files as plain text, annotated with line numbers and hit counts.
+//!
+//! The output is similar to the text mode of `llvm-cov show`, and meant for quick reviews in the
+//! terminal, or to be attached to tickets, where the HTML report can't be used.
+
+use std::io::{self, Write};
+
+use crate::templates::{Coverage, FileInfo};
+
+/// Print the given source code, with a gutter showing line numbers, hit counts and markers for
+/// (partially) uncovered lines.
+///
+/// If `context` is set, only uncovered lines are printed, surrounded by the given amount of lines
+/// before and after them. Files without any uncovered lines are skipped completely in that case.
+pub fn print(
+    out: &mut impl Write,
+    info: &FileInfo,
+    source: &str,
+    overlay_instantiations: bool,
+    context: Option<usize>,
+) -> io::Result<()> {
+    let lines = source.lines().collect::<Vec<_>>();
+    let coverage = (1..=lines.len())
+        .map(|line| info.coverage(line, overlay_instantiations))
+        .collect::<Vec<_>>();
+
+    let hunks = match context {
+        Some(context) => uncovered_hunks(&coverage, context),
+        None if lines.is_empty() => Vec::new(),
+        None => vec![(0, lines.len() - 1)],
+    };
+
+    if hunks.is_empty() {
+        return Ok(());
+    }
+
+    let width = lines.len().to_string().len();
+
+    writeln!(out, "{}:", info.relative_path)?;
+
+    for (i, &(start, end)) in hunks.iter().enumerate() {
+        if i > 0 {
+            writeln!(out, "{:>width$} |", "...")?;
+        }
+
+        for index in start..=end {
+            let (count, marker) = match coverage[index] {
+                Coverage::Covered(count) => (count.to_string(), ' '),
+                Coverage::Uncovered(0) => ("0".to_owned(), '!'),
+                Coverage::Uncovered(count) => (count.to_string(), '~'),
+                Coverage::Unknown => (String::new(), ' '),
+            };
+
+            write!(out, "{:>width$} | {count:>8} {marker} |", index + 1)?;
+
+            if lines[index].is_empty() {
+                writeln!(out)?;
+            } else {
+                writeln!(out, " {}", lines[index])?;
+            }
+        }
+    }
+
+    writeln!(out)
+}
+
+/// Find all ranges of uncovered lines (as zero-based, inclusive indices) and extend them by the
+/// given amount of context lines. Ranges that overlap or touch each other are merged.
+pub fn uncovered_hunks(coverage: &[Coverage], context: usize) -> Vec<(usize, usize)> {
+    let last = coverage.len().saturating_sub(1);
+
+    coverage
+        .iter()
+        .enumerate()
+        .filter(|(_, coverage)| matches!(coverage, Coverage::Uncovered(_)))
+        .map(|(index, _)| (index.saturating_sub(context), (index + context).min(last)))
+        .fold(
+            Vec::new(),
+            |mut hunks: Vec<(usize, usize)>, (start, end)| {
+                match hunks.last_mut() {
+                    Some(hunk) if start <= hunk.1 + 1 => hunk.1 = end,
+                    _ => hunks.push((start, end)),
+                }
+                hunks
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use indoc::indoc;
+
+    use crate::templates::FileInfo;
+
+    fn info() -> FileInfo {
+        FileInfo {
+            covered: HashMap::from([(1, 2), (2, 2), (6, 1)]),
+            uncovered: HashSet::from([3, 6]),
+            ..FileInfo::fixture("src/file.rs")
+        }
+    }
+
+    const CONTENT: &str = indoc! {"
+        fn main() {
+            if false {
+                unreachable!();
+            }
+        }
+    "};
+
+    #[test]
+    fn print_full() {
+        let mut output = Vec::new();
+        super::print(&mut output, &info(), CONTENT, true, None).unwrap();
+
+        let expect = indoc! {"
+            src/file.rs:
+            1 |        2   | fn main() {
+            2 |        2   |     if false {
+            3 |        0 ! |         unreachable!();
+            4 |            |     }
+            5 |            | }
+
+        "};
+
+        assert_eq!(expect, String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn print_uncovered_only() {
+        let mut output = Vec::new();
+        super::print(&mut output, &info(), CONTENT, true, Some(0)).unwrap();
+
+        let expect = indoc! {"
+            src/file.rs:
+            3 |        0 ! |         unreachable!();
+
+        "};
+
+        assert_eq!(expect, String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn merge_hunks() {
+        use crate::templates::Coverage::{Covered, Uncovered, Unknown};
+
+        let coverage = [
+            Uncovered(0),
+            Covered(1),
+            Covered(1),
+            Uncovered(1),
+            Unknown,
+            Unknown,
+            Unknown,
+            Uncovered(0),
+        ];
+
+        assert_eq!(vec![(0, 4), (6, 7)], super::uncovered_hunks(&coverage, 1));
+        assert_eq!(vec![(0, 7)], super::uncovered_hunks(&coverage, 2));
+    }
+}